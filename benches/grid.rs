@@ -0,0 +1,68 @@
+//! Times `Grid` lookups on a 500x500 board against a scan of the move log, which is what
+//! every lookup of an empty tile used to cost.
+//!
+//! Run with `cargo bench --bench grid`.
+
+use std::time::{ Duration, Instant };
+
+use tictactoe_client::{ grid::Grid, protocol::{ PlayerMove, Size } };
+
+const SIZE: i32 = 500;
+const MOVES: usize = 5000;
+
+/// Owner of a tile found by scanning the moves from the last one.
+fn scan(moves: &[PlayerMove], pos: &Size) -> Option<i32> {
+    moves
+        .iter()
+        .rev()
+        .find(|m| m.position == *pos)
+        .map(|m| m.player)
+}
+
+/// Same positions on every run, spread over the board.
+fn moves() -> Vec<PlayerMove> {
+    let mut seed: u32 = 12345;
+    (0..MOVES)
+        .map(|i| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let x = ((seed >> 8) % (SIZE as u32)) as i32;
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let y = ((seed >> 8) % (SIZE as u32)) as i32;
+            PlayerMove { player: (i % 3) as i32, position: Size::new(x, y) }
+        })
+        .collect()
+}
+
+/// Reads every tile of the board once, returns the time taken and the number of owned tiles.
+fn read_board(get: impl Fn(&Size) -> Option<i32>) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut owned = 0;
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if get(&Size::new(x, y)).is_some() {
+                owned += 1;
+            }
+        }
+    }
+    (start.elapsed(), owned)
+}
+
+fn main() {
+    let start = Instant::now();
+    let grid = Grid::new(Size::new(SIZE, SIZE), moves());
+    println!("build {}x{} with {} moves: {:?}", SIZE, SIZE, MOVES, start.elapsed());
+
+    let (dense, dense_owned) = read_board(|pos| grid.get_pos(pos));
+    let (scanned, scan_owned) = read_board(|pos| scan(grid.moves(), pos));
+    assert_eq!(dense_owned, scan_owned);
+
+    println!("read board, dense cells: {:?}", dense);
+    println!("read board, move log scan: {:?}", scanned);
+    println!("speedup: {:.0}x", scanned.as_secs_f64() / dense.as_secs_f64().max(1e-9));
+
+    let start = Instant::now();
+    for m in grid.moves() {
+        grid.check_win_at(&m.position, 5);
+    }
+    println!("check_win_at after every move: {:?}", start.elapsed());
+}
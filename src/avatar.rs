@@ -0,0 +1,140 @@
+use std::{ cell::RefCell, fmt, rc::Rc };
+
+use serde::Serialize;
+use wasm_bindgen::{ prelude::*, JsCast };
+use web_sys::{ CanvasRenderingContext2d, File, HtmlCanvasElement, HtmlImageElement, Url };
+
+use crate::utils::document;
+
+/// Side of the uploaded avatar, matching the tile textures.
+pub const AVATAR_SIZE: u32 = 16;
+/// Largest image file accepted before decoding, in bytes.
+pub const MAX_FILE_SIZE: f64 = 5.0 * 1024.0 * 1024.0;
+const PNG_PREFIX: &str = "data:image/png;base64,";
+
+/// Content of `set_image`.
+#[derive(Serialize, Clone, Debug)]
+pub struct AvatarUpload {
+    /// Base64 encoded PNG.
+    pub image: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AvatarError {
+    NotAnImage,
+    TooLarge,
+    /// The browser could not decode the file.
+    Unreadable,
+}
+impl fmt::Display for AvatarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AvatarError::NotAnImage => write!(f, "File is not an image"),
+            AvatarError::TooLarge =>
+                write!(f, "Image is larger than {} MB", MAX_FILE_SIZE / 1024.0 / 1024.0),
+            AvatarError::Unreadable => write!(f, "Image could not be read"),
+        }
+    }
+}
+
+/// Largest centered square of an image.
+#[derive(Debug, PartialEq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub side: u32,
+}
+
+pub fn crop_square(width: u32, height: u32) -> Option<Crop> {
+    let side = width.min(height);
+    if side == 0 {
+        return None;
+    }
+    Some(Crop {
+        x: (width - side) / 2,
+        y: (height - side) / 2,
+        side,
+    })
+}
+
+/// Base64 data of a PNG data URL.
+pub fn png_base64(data_url: &str) -> Option<&str> {
+    data_url.strip_prefix(PNG_PREFIX).filter(|data| !data.is_empty())
+}
+
+type OnLoad = Box<dyn FnOnce(Result<String, AvatarError>)>;
+
+/// Crops and downscales an image file to an avatar, passed to `on_load` as base64 PNG.
+pub fn load_avatar(file: &File, on_load: impl FnOnce(Result<String, AvatarError>) + 'static) {
+    if !file.type_().starts_with("image/") {
+        on_load(Err(AvatarError::NotAnImage));
+        return;
+    }
+    if file.size() > MAX_FILE_SIZE {
+        on_load(Err(AvatarError::TooLarge));
+        return;
+    }
+
+    let url = Url::create_object_url_with_blob(file).expect("Unable to create object URL");
+    let image = HtmlImageElement::new().expect("Could not create image element.");
+    // Only one of load and error fires
+    let on_load: Rc<RefCell<Option<OnLoad>>> = Rc::new(RefCell::new(Some(Box::new(on_load))));
+
+    let loaded = image.clone();
+    let loaded_url = url.clone();
+    let on_success = on_load.clone();
+    let success = Closure::once_into_js(move || {
+        let _ = Url::revoke_object_url(&loaded_url);
+        if let Some(on_load) = on_success.borrow_mut().take() {
+            on_load(encode_avatar(&loaded));
+        }
+    });
+    let failed_url = url.clone();
+    let failure = Closure::once_into_js(move || {
+        let _ = Url::revoke_object_url(&failed_url);
+        if let Some(on_load) = on_load.borrow_mut().take() {
+            on_load(Err(AvatarError::Unreadable));
+        }
+    });
+    image.set_onload(Some(success.unchecked_ref()));
+    image.set_onerror(Some(failure.unchecked_ref()));
+    image.set_src(&url);
+}
+
+fn encode_avatar(image: &HtmlImageElement) -> Result<String, AvatarError> {
+    let crop = crop_square(image.natural_width(), image.natural_height()).ok_or(
+        AvatarError::Unreadable
+    )?;
+
+    let canvas: HtmlCanvasElement = document()
+        .create_element("canvas")
+        .expect("Unable to create canvas")
+        .dyn_into()
+        .expect("Not HtmlCanvasElement type");
+    canvas.set_width(AVATAR_SIZE);
+    canvas.set_height(AVATAR_SIZE);
+    let context: CanvasRenderingContext2d = canvas
+        .get_context("2d")
+        .ok()
+        .flatten()
+        .expect("Missing 2d context")
+        .dyn_into()
+        .expect("Not CanvasRenderingContext2d type");
+
+    context
+        .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+            image,
+            f64::from(crop.x),
+            f64::from(crop.y),
+            f64::from(crop.side),
+            f64::from(crop.side),
+            0.0,
+            0.0,
+            f64::from(AVATAR_SIZE),
+            f64::from(AVATAR_SIZE)
+        )
+        .map_err(|_| AvatarError::Unreadable)?;
+
+    let data_url = canvas.to_data_url().map_err(|_| AvatarError::Unreadable)?;
+    png_base64(&data_url).map(str::to_string).ok_or(AvatarError::Unreadable)
+}
@@ -0,0 +1,190 @@
+//! Compact binary encoding for the largest server messages.
+//!
+//! Every message starts with the format version, a message kind byte and
+//! the id of the request it answers, 0 if none or the id + 1. Integers are
+//! LEB128 varints, signed ones zigzag encoded first, and strings are a
+//! varint byte length followed by UTF-8.
+
+use crate::{
+    grid::Grid,
+    pendingrequests::RequestId,
+    player::Player,
+    playermove::PlayerMove,
+    protocol::{ ProtocolError, ServerEvent, ServerMessage },
+    utils::Size,
+};
+
+pub const BINARY_VERSION: u8 = 2;
+
+const KIND_NEW_MOVE: u8 = 1;
+const KIND_CURRENT_STATE: u8 = 2;
+const KIND_PLAYERS: u8 = 3;
+
+pub fn encode_move(m: &PlayerMove, request_id: Option<RequestId>) -> Vec<u8> {
+    let mut writer = Writer::new(KIND_NEW_MOVE, request_id);
+    writer.write_move(m);
+    writer.bytes
+}
+
+pub fn encode_grid(grid: &Grid, request_id: Option<RequestId>) -> Vec<u8> {
+    let mut writer = Writer::new(KIND_CURRENT_STATE, request_id);
+    writer.write_i32(grid.size.x);
+    writer.write_i32(grid.size.y);
+    writer.write_u32(grid.moves().len() as u32);
+    for m in grid.moves() {
+        writer.write_move(m);
+    }
+    writer.bytes
+}
+
+pub fn encode_players(players: &[Player], request_id: Option<RequestId>) -> Vec<u8> {
+    let mut writer = Writer::new(KIND_PLAYERS, request_id);
+    writer.write_u32(players.len() as u32);
+    for p in players {
+        writer.write_u32(p.id);
+        // 0 means not in a game, otherwise the game id + 1
+        writer.write_u32(p.joined_game_id.map_or(0, |id| id + 1));
+        writer.write_u8(p.ready as u8);
+        writer.write_str(&p.name);
+    }
+    writer.bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<ServerMessage, ProtocolError> {
+    let mut reader = Reader::new(bytes);
+
+    let version = reader.read_u8()?;
+    if version != BINARY_VERSION {
+        return Err(
+            ProtocolError::InvalidBinary(format!("unsupported binary version {}", version))
+        );
+    }
+
+    let kind = reader.read_u8()?;
+    let request_id = reader.read_u32()?.checked_sub(1);
+    let event = match kind {
+        KIND_NEW_MOVE => ServerEvent::NewMove(reader.read_move()?),
+        KIND_CURRENT_STATE => {
+            let size = Size::new(reader.read_i32()?, reader.read_i32()?);
+            let count = reader.read_len()?;
+            let mut moves = Vec::with_capacity(count);
+            for _ in 0..count {
+                moves.push(reader.read_move()?);
+            }
+            ServerEvent::CurrentState(Grid::new(size, moves))
+        }
+        KIND_PLAYERS => {
+            let count = reader.read_len()?;
+            let mut players = Vec::with_capacity(count);
+            for _ in 0..count {
+                let id = reader.read_u32()?;
+                let joined_game_id = reader.read_u32()?.checked_sub(1);
+                let ready = reader.read_u8()? != 0;
+                let name = reader.read_str()?;
+                players.push(Player::new(id, name, joined_game_id, ready));
+            }
+            ServerEvent::Players(players)
+        }
+        kind => {
+            return Err(ProtocolError::UnknownEvent(format!("binary message kind {}", kind)));
+        }
+    };
+
+    if !reader.is_at_end() {
+        return Err(ProtocolError::InvalidBinary("trailing bytes after message".to_string()));
+    }
+    Ok(ServerMessage { request_id, event })
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+impl Writer {
+    fn new(kind: u8, request_id: Option<RequestId>) -> Self {
+        let mut writer = Self {
+            bytes: vec![BINARY_VERSION, kind],
+        };
+        writer.write_u32(request_id.map_or(0, |id| id + 1));
+        writer
+    }
+    fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+    fn write_u32(&mut self, mut value: u32) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+    fn write_i32(&mut self, value: i32) {
+        self.write_u32(((value << 1) ^ (value >> 31)) as u32);
+    }
+    fn write_str(&mut self, value: &str) {
+        self.write_u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+    fn write_move(&mut self, m: &PlayerMove) {
+        self.write_i32(m.player);
+        self.write_i32(m.position.x);
+        self.write_i32(m.position.y);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+    fn is_at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+    fn read_u8(&mut self) -> Result<u8, ProtocolError> {
+        let value = *self.bytes
+            .get(self.pos)
+            .ok_or_else(|| ProtocolError::InvalidBinary("unexpected end of message".to_string()))?;
+        self.pos += 1;
+        Ok(value)
+    }
+    fn read_u32(&mut self) -> Result<u32, ProtocolError> {
+        let mut value: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.read_u8()?;
+            // Only the low four bits of the fifth byte fit in a u32
+            if shift == 28 && byte > 0x0f {
+                return Err(ProtocolError::InvalidBinary("varint out of range".to_string()));
+            }
+            value |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ProtocolError::InvalidBinary("varint too long".to_string()))
+    }
+    fn read_i32(&mut self) -> Result<i32, ProtocolError> {
+        let value = self.read_u32()?;
+        Ok(((value >> 1) as i32) ^ -((value & 1) as i32))
+    }
+    /// Reads a collection length, refusing lengths longer than the remaining input.
+    fn read_len(&mut self) -> Result<usize, ProtocolError> {
+        let len = self.read_u32()? as usize;
+        if len > self.bytes.len() - self.pos {
+            return Err(ProtocolError::InvalidBinary(format!("length {} out of range", len)));
+        }
+        Ok(len)
+    }
+    fn read_str(&mut self) -> Result<String, ProtocolError> {
+        let len = self.read_len()?;
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        String::from_utf8(bytes.to_vec()).map_err(|e| ProtocolError::InvalidBinary(e.to_string()))
+    }
+    fn read_move(&mut self) -> Result<PlayerMove, ProtocolError> {
+        let player = self.read_i32()?;
+        let position = Size::new(self.read_i32()?, self.read_i32()?);
+        Ok(PlayerMove { player, position })
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    encodingoptions::EncodingOptions,
+    heartbeat::Heartbeat,
+    hello::Hello,
+    outboundqueue::{ DropPolicy, OutboundQueue },
+    pendingrequests::{ OnReply, Outcome, PendingRequests, Reply, RequestId },
+    protocol::{ ClientEvent, ServerEvent, ServerMessage },
+};
+
+/// Time after which a written request is given up on.
+const REQUEST_TIMEOUT_MS: f64 = 10000.0;
+/// Interval between heartbeat pings.
+const PING_INTERVAL_MS: i32 = 5000;
+/// Pings in a row without a pong after which the connection counts as dead.
+const MAX_MISSED_PINGS: u32 = 2;
+/// Messages kept while the connection is down.
+const QUEUE_CAPACITY: usize = 256;
+
+/// Writes an encoded message to the underlying socket.
+pub type Writer = Box<dyn Fn(&str) -> Result<(), String>>;
+
+/// Encoded message, with the id of the request it carries if any.
+struct Outbound {
+    text: String,
+    request: Option<RequestId>,
+}
+
+/// What became of a received message.
+pub struct Received {
+    /// Requests the message answered.
+    pub replies: Vec<Reply>,
+    /// Whether the message is left for the session, pongs and errors that
+    /// answered a request are not.
+    pub forward: bool,
+}
+
+/// What came due on a `tick`.
+pub struct Tick {
+    /// Requests that timed out, or were lost with a dead connection.
+    pub replies: Vec<Reply>,
+    /// Whether too many pings went unanswered, the channel is closed then.
+    pub dead: bool,
+}
+
+/// Protocol state of a connection to the server, without any socket or timer.
+///
+/// Queues messages while closed, matches replies to requests, times requests
+/// out and schedules heartbeat pings. Transports own a channel, hand it the
+/// messages they receive and call `tick` regularly. Times are in milliseconds.
+/// Finished requests are handed back as `Reply`s, to be run once the channel
+/// is no longer borrowed.
+pub struct Channel {
+    writer: Writer,
+    open: bool,
+    queue: OutboundQueue<Outbound>,
+    requests: PendingRequests,
+    /// Whether the server echoes request ids, as told by its `Hello`.
+    request_ids: bool,
+    heartbeat: Heartbeat,
+    /// `None` unless the server answers pings.
+    next_ping: Option<f64>,
+}
+impl Channel {
+    /// Creates a closed channel writing through `writer`.
+    pub fn new(writer: Writer) -> Self {
+        Self {
+            writer,
+            open: false,
+            queue: OutboundQueue::new(QUEUE_CAPACITY, DropPolicy::DropOldest),
+            requests: PendingRequests::new(REQUEST_TIMEOUT_MS),
+            request_ids: false,
+            heartbeat: Heartbeat::new(PING_INTERVAL_MS, MAX_MISSED_PINGS),
+            next_ping: None,
+        }
+    }
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+    /// Marks the socket as open. Writes `handshake` right away, then the
+    /// queued messages.
+    pub fn opened(&mut self, handshake: &[ClientEvent], now: f64) -> Vec<Reply> {
+        self.open = true;
+        self.heartbeat.reset();
+        self.next_ping = None;
+        for event in handshake {
+            // A failed write closes the socket, the next one redoes the handshake
+            let _ = (self.writer)(&event.encode());
+        }
+        self.flush(now)
+    }
+    /// Marks the socket as gone, failing the requests it will never answer.
+    pub fn closed(&mut self) -> Vec<Reply> {
+        self.open = false;
+        self.next_ping = None;
+        self.requests.disconnected()
+    }
+    /// Forgets what the previous server supported.
+    pub fn reset_features(&mut self) {
+        self.request_ids = false;
+        self.next_ping = None;
+    }
+    /// Writes the message, or queues it until the channel is open. Queued
+    /// messages are written in order.
+    pub fn send(&mut self, text: String, now: f64) -> Vec<Reply> {
+        self.send_message(Outbound { text, request: None }, now)
+    }
+    /// Sends a command with a request id, `on_reply` is handed back with the
+    /// server's answer. Servers that don't echo request ids get the plain
+    /// command and the reply is `Sent` right away.
+    pub fn request(&mut self, event: &ClientEvent, on_reply: OnReply, now: f64) -> Vec<Reply> {
+        if !self.request_ids {
+            let id = self.requests.insert(event.name(), on_reply);
+            let mut replies = self.send(event.encode(), now);
+            replies.extend(self.requests.fail(id, Outcome::Sent));
+            return replies;
+        }
+        let id = self.requests.insert(event.name(), on_reply);
+        self.send_message(Outbound { text: event.encode_request(id), request: Some(id) }, now)
+    }
+    /// Turns on the optional features the server lists in its `Hello`.
+    /// Servers that don't answer pings would look dead, only those that list
+    /// `heartbeat` get them, and only those that list `binary` are asked for
+    /// binary frames.
+    pub fn server_hello(&mut self, hello: &Hello, now: f64) -> Vec<Reply> {
+        self.request_ids = hello.supports("request_id");
+        self.next_ping = if hello.supports("heartbeat") {
+            Some(now + (self.heartbeat.interval_ms() as f64))
+        } else {
+            None
+        };
+        if !hello.supports("binary") {
+            return Vec::new();
+        }
+        self.send(ClientEvent::Encoding(EncodingOptions::binary()).encode(), now)
+    }
+    /// Handles a message from the server before the session sees it.
+    pub fn receive(&mut self, message: &ServerMessage, now: f64) -> Received {
+        let mut replies = match &message.event {
+            ServerEvent::Hello(hello) => self.server_hello(hello, now),
+            _ => Vec::new(),
+        };
+        let reply = self.requests.resolve(message);
+        let forward = match &message.event {
+            ServerEvent::Pong(pong) => {
+                self.heartbeat.pong(pong, now);
+                false
+            }
+            // Reported by the request's callback
+            ServerEvent::Error(_) => reply.is_none(),
+            _ => true,
+        };
+        replies.extend(reply);
+        Received { replies, forward }
+    }
+    /// Times out unanswered requests and sends the heartbeat ping if it is due.
+    pub fn tick(&mut self, now: f64) -> Tick {
+        let mut replies = self.requests.expire(now);
+        let due = self.open && self.next_ping.is_some_and(|next_ping| next_ping <= now);
+        if !due {
+            return Tick { replies, dead: false };
+        }
+        let ping = self.heartbeat.ping(now);
+        if self.heartbeat.is_dead() {
+            replies.extend(self.closed());
+            return Tick { replies, dead: true };
+        }
+        self.next_ping = Some(now + (self.heartbeat.interval_ms() as f64));
+        replies.extend(self.send(ClientEvent::Ping(ping).encode(), now));
+        Tick { replies, dead: false }
+    }
+    /// When the next heartbeat ping is due, if the server answers them.
+    pub fn next_ping(&self) -> Option<f64> {
+        self.next_ping
+    }
+    pub fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
+    /// Number of messages waiting for the channel to open.
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+    /// Number of requests waiting for a reply.
+    pub fn pending_requests(&self) -> usize {
+        self.requests.len()
+    }
+
+    fn flush(&mut self, now: f64) -> Vec<Reply> {
+        let mut replies = Vec::new();
+        while let Some(message) = self.queue.pop() {
+            if self.write_message(&message, now).is_err() {
+                replies.extend(self.requeue(message));
+                break;
+            }
+        }
+        replies
+    }
+
+    fn send_message(&mut self, message: Outbound, now: f64) -> Vec<Reply> {
+        if self.open && self.queue.is_empty() {
+            if self.write_message(&message, now).is_err() {
+                return self.requeue(message).into_iter().collect();
+            }
+            return Vec::new();
+        }
+        let dropped = self.queue.push(message);
+        dropped.and_then(|message| self.drop_message(message)).into_iter().collect()
+    }
+
+    /// Writes a message, starting the timeout of the request it carries.
+    fn write_message(&mut self, message: &Outbound, now: f64) -> Result<(), String> {
+        (self.writer)(&message.text)?;
+        if let Some(id) = message.request {
+            self.requests.sent(id, now);
+        }
+        Ok(())
+    }
+
+    fn requeue(&mut self, message: Outbound) -> Option<Reply> {
+        let dropped = self.queue.requeue(message)?;
+        self.drop_message(dropped)
+    }
+
+    fn drop_message(&mut self, message: Outbound) -> Option<Reply> {
+        self.requests.fail(message.request?, Outcome::Disconnected)
+    }
+}
@@ -0,0 +1,108 @@
+use std::{ collections::{ HashMap, VecDeque }, fmt };
+
+use serde::{ Deserialize, Serialize };
+
+/// Longest message accepted, in characters.
+pub const MAX_LENGTH: usize = 300;
+/// Messages kept per game.
+const HISTORY_LIMIT: usize = 200;
+
+/// Content of the `chat` event sent by the client.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChatRequest {
+    pub game_id: u32,
+    pub text: String,
+}
+
+/// Content of the `chat` event broadcast by the server.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub game_id: u32,
+    /// Id of the sending player.
+    pub player: u32,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ChatError {
+    Empty,
+    TooLong,
+    /// Too many messages sent recently, retry after the given milliseconds.
+    RateLimited(f64),
+}
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatError::Empty => write!(f, "Message is empty"),
+            ChatError::TooLong => write!(f, "Message is longer than {} characters", MAX_LENGTH),
+            ChatError::RateLimited(wait) =>
+                write!(f, "Sending too fast, wait {:.0} seconds", (wait / 1000.0).ceil()),
+        }
+    }
+}
+
+/// Chat messages received so far, per game.
+#[derive(Default, Debug)]
+pub struct ChatHistory {
+    games: HashMap<u32, VecDeque<ChatMessage>>,
+}
+impl ChatHistory {
+    pub fn push(&mut self, message: ChatMessage) {
+        let messages = self.games.entry(message.game_id).or_default();
+        if messages.len() == HISTORY_LIMIT {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+    }
+    pub fn messages(&self, game_id: u32) -> impl Iterator<Item = &ChatMessage> {
+        self.games.get(&game_id).into_iter().flatten()
+    }
+    pub fn clear(&mut self, game_id: u32) {
+        self.games.remove(&game_id);
+    }
+}
+
+/// Allows at most `max` messages in any `window_ms` long period.
+#[derive(Debug)]
+pub struct RateLimiter {
+    max: usize,
+    window_ms: f64,
+    sent: VecDeque<f64>,
+}
+impl RateLimiter {
+    pub fn new(max: usize, window_ms: f64) -> Self {
+        Self {
+            max,
+            window_ms,
+            sent: VecDeque::with_capacity(max),
+        }
+    }
+    /// Records a message sent at `now`, or returns how long to wait before one is allowed.
+    pub fn try_send(&mut self, now: f64) -> Result<(), f64> {
+        while self.sent.front().is_some_and(|&t| now - t >= self.window_ms) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.max {
+            return Err(self.sent[0] + self.window_ms - now);
+        }
+        self.sent.push_back(now);
+        Ok(())
+    }
+}
+
+/// Validates a message typed by the player and checks the rate limit.
+pub fn prepare_message(
+    text: &str,
+    limiter: &mut RateLimiter,
+    now: f64
+) -> Result<String, ChatError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(ChatError::Empty);
+    }
+    if text.chars().count() > MAX_LENGTH {
+        return Err(ChatError::TooLong);
+    }
+    limiter.try_send(now).map_err(ChatError::RateLimited)?;
+    Ok(text.to_string())
+}
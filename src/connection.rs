@@ -0,0 +1,398 @@
+use std::{ cell::{ Cell, RefCell }, rc::{ Rc, Weak } };
+
+use wasm_bindgen::{ closure::Closure, prelude::*, JsCast };
+use web_sys::{ CloseEvent, ErrorEvent, MessageEvent, WebSocket };
+
+use crate::{
+    channel::Channel,
+    error,
+    heartbeat::Heartbeat,
+    hello::Hello,
+    log,
+    pendingrequests::{ Outcome, Reply },
+    protocol::ClientEvent,
+    transport::Transport,
+    utils::{ get_element_by_id, now, set_interval, set_timeout, window },
+    warn,
+};
+
+/// Interval between checks for due pings and expired requests.
+const TICK_MS: i32 = 1000;
+
+thread_local! {
+    static ACTIVE: RefCell<Weak<ConnectionInner>> = const { RefCell::new(Weak::new()) };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConnectionState {
+    Connecting,
+    Open,
+    Reconnecting,
+}
+impl ConnectionState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "Connecting...",
+            ConnectionState::Open => "Connected",
+            ConnectionState::Reconnecting => "Connection lost, reconnecting...",
+        }
+    }
+    pub fn class_name(&self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Open => "open",
+            ConnectionState::Reconnecting => "reconnecting",
+        }
+    }
+}
+
+/// Exponential backoff for reconnection attempts.
+#[derive(Debug)]
+pub struct Backoff {
+    initial_ms: i32,
+    max_ms: i32,
+    attempts: u32,
+}
+impl Backoff {
+    pub fn new(initial_ms: i32, max_ms: i32) -> Self {
+        Self {
+            initial_ms,
+            max_ms,
+            attempts: 0,
+        }
+    }
+    /// Returns the delay before the next attempt and counts the attempt.
+    pub fn next_delay(&mut self) -> i32 {
+        let delay = self.initial_ms
+            .saturating_mul((2i32).saturating_pow(self.attempts))
+            .min(self.max_ms);
+        self.attempts += 1;
+        delay
+    }
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// Handle to the server connection. Replaces the underlying `WebSocket` when
+/// it closes, so clones stay valid across reconnects.
+#[derive(Clone)]
+pub(crate) struct Connection {
+    inner: Rc<ConnectionInner>,
+}
+
+struct ConnectionInner {
+    /// `None` until a usable server address is set.
+    url: RefCell<Option<String>>,
+    /// Shared with the channel's writer.
+    socket: Rc<RefCell<Option<WebSocket>>>,
+    state: Cell<ConnectionState>,
+    backoff: RefCell<Backoff>,
+    reconnect_timer: Cell<Option<i32>>,
+    channel: RefCell<Channel>,
+    tick_timer: Cell<Option<i32>>,
+    callbacks: RefCell<Option<Callbacks>>,
+    /// Brings the session back after a reconnect.
+    on_resume: Box<dyn Fn(&Connection)>,
+}
+
+struct Callbacks {
+    onmessage: Closure<dyn FnMut(MessageEvent)>,
+    onopen: Closure<dyn FnMut()>,
+    onclose: Closure<dyn FnMut(CloseEvent)>,
+    onerror: Closure<dyn FnMut(ErrorEvent)>,
+    reconnect: Closure<dyn FnMut()>,
+    tick: Closure<dyn FnMut()>,
+}
+
+impl Connection {
+    pub fn new(
+        url: Option<String>,
+        mut on_message: impl FnMut(&Connection, MessageEvent) + 'static,
+        on_resume: impl Fn(&Connection) + 'static
+    ) -> Self {
+        let socket = Rc::new(RefCell::new(None::<WebSocket>));
+        let writer = socket.clone();
+        let channel = Channel::new(
+            Box::new(move |text: &str| {
+                match writer.borrow().as_ref() {
+                    Some(socket) => socket.send_with_str(text).map_err(|err| format!("{:?}", err)),
+                    None => Err("No socket".to_string()),
+                }
+            })
+        );
+        let inner = Rc::new(ConnectionInner {
+            url: RefCell::new(url),
+            socket,
+            state: Cell::new(ConnectionState::Connecting),
+            backoff: RefCell::new(Backoff::new(500, 30000)),
+            reconnect_timer: Cell::new(None),
+            channel: RefCell::new(channel),
+            tick_timer: Cell::new(None),
+            callbacks: RefCell::new(None),
+            on_resume: Box::new(on_resume),
+        });
+
+        let weak = Rc::downgrade(&inner);
+        let onmessage = Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| {
+            if let Some(connection) = Connection::upgrade(&weak) {
+                on_message(&connection, e);
+            }
+        });
+
+        let weak = Rc::downgrade(&inner);
+        let onopen = Closure::<dyn FnMut()>::new(move || {
+            if let Some(connection) = Connection::upgrade(&weak) {
+                connection.on_open();
+            }
+        });
+
+        let weak = Rc::downgrade(&inner);
+        let onclose = Closure::<dyn FnMut(_)>::new(move |e: CloseEvent| {
+            log!("socket closed: {} {}", e.code(), e.reason());
+            if let Some(connection) = Connection::upgrade(&weak) {
+                connection.detach_socket();
+                connection.schedule_reconnect();
+            }
+        });
+
+        let onerror = Closure::<dyn FnMut(_)>::new(move |e: ErrorEvent| {
+            log!("error event: {:?}", e);
+        });
+
+        let weak = Rc::downgrade(&inner);
+        let reconnect = Closure::<dyn FnMut()>::new(move || {
+            if let Some(connection) = Connection::upgrade(&weak) {
+                connection.connect();
+            }
+        });
+
+        let weak = Rc::downgrade(&inner);
+        let tick = Closure::<dyn FnMut()>::new(move || {
+            if let Some(connection) = Connection::upgrade(&weak) {
+                connection.tick();
+            }
+        });
+
+        *inner.callbacks.borrow_mut() = Some(Callbacks {
+            onmessage,
+            onopen,
+            onclose,
+            onerror,
+            reconnect,
+            tick,
+        });
+
+        ACTIVE.with(|active| {
+            *active.borrow_mut() = Rc::downgrade(&inner);
+        });
+
+        let connection = Self { inner };
+        connection.connect();
+        connection
+    }
+
+    fn upgrade(weak: &Weak<ConnectionInner>) -> Option<Connection> {
+        weak.upgrade().map(|inner| Connection { inner })
+    }
+
+    /// The most recently created connection, if it is still alive.
+    pub fn active() -> Option<Connection> {
+        ACTIVE.with(|active| Connection::upgrade(&active.borrow()))
+    }
+
+    /// Switches to a different server, dropping the current socket.
+    pub fn set_url(&self, url: &str) {
+        *self.inner.url.borrow_mut() = Some(url.to_string());
+        // Until the new server says otherwise
+        self.inner.channel.borrow_mut().reset_features();
+        self.inner.backoff.borrow_mut().reset();
+        self.set_state(ConnectionState::Connecting);
+        self.connect();
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.inner.state.get()
+    }
+
+    /// Number of messages waiting for the socket to open.
+    pub fn queue_len(&self) -> usize {
+        self.inner.channel.borrow().queue_len()
+    }
+
+    fn connect(&self) {
+        if let Some(timer) = self.inner.reconnect_timer.take() {
+            window().clear_timeout_with_handle(timer);
+        }
+        self.detach_socket();
+
+        let url = match self.inner.url.borrow().clone() {
+            Some(url) => url,
+            None => {
+                warn!("No server address, not connecting");
+                return;
+            }
+        };
+        log!("Connecting to {}", url);
+        let socket = match WebSocket::new(url.as_str()) {
+            Ok(socket) => socket,
+            Err(err) => {
+                error!("Unable to create socket: {:?}", err);
+                self.schedule_reconnect();
+                return;
+            }
+        };
+        socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+
+        {
+            let callbacks = self.inner.callbacks.borrow();
+            let callbacks = callbacks.as_ref().expect("Callbacks not initialized");
+            socket.set_onmessage(Some(callbacks.onmessage.as_ref().unchecked_ref()));
+            socket.set_onopen(Some(callbacks.onopen.as_ref().unchecked_ref()));
+            socket.set_onclose(Some(callbacks.onclose.as_ref().unchecked_ref()));
+            socket.set_onerror(Some(callbacks.onerror.as_ref().unchecked_ref()));
+        }
+
+        *self.inner.socket.borrow_mut() = Some(socket);
+        if self.state() != ConnectionState::Reconnecting {
+            self.set_state(ConnectionState::Connecting);
+        }
+    }
+
+    /// Unhooks the previous socket so a late event from it can't reach us,
+    /// and fails the requests it will never answer.
+    fn detach_socket(&self) {
+        self.stop_ticking();
+        if let Some(old) = self.inner.socket.borrow_mut().take() {
+            old.set_onmessage(None);
+            old.set_onopen(None);
+            old.set_onclose(None);
+            old.set_onerror(None);
+            let _ = old.close();
+        }
+        let lost = self.inner.channel.borrow_mut().closed();
+        self.run_replies(lost);
+    }
+
+    fn on_open(&self) {
+        log!("socket opened");
+        let reconnected = self.inner.backoff.borrow().attempts() > 0;
+        self.inner.backoff.borrow_mut().reset();
+        self.set_state(ConnectionState::Open);
+
+        let mut handshake = vec![ClientEvent::Hello(Hello::client()), ClientEvent::Players];
+        if reconnected {
+            // Refresh everything that may have changed while we were disconnected
+            log!("Resuming session");
+            handshake.push(ClientEvent::Games);
+        }
+        let queued = self.queue_len();
+        if queued > 0 {
+            log!("Sending {} queued messages", queued);
+        }
+        let failed = self.inner.channel.borrow_mut().opened(&handshake, now());
+        self.run_replies(failed);
+        self.start_ticking();
+        if reconnected {
+            (self.inner.on_resume)(self);
+        }
+    }
+
+    fn start_ticking(&self) {
+        self.stop_ticking();
+        display_latency(self.inner.channel.borrow().heartbeat());
+
+        let callbacks = self.inner.callbacks.borrow();
+        let timer = set_interval(
+            &callbacks.as_ref().expect("Callbacks not initialized").tick,
+            TICK_MS
+        );
+        self.inner.tick_timer.set(Some(timer));
+    }
+
+    fn stop_ticking(&self) {
+        if let Some(timer) = self.inner.tick_timer.take() {
+            window().clear_interval_with_handle(timer);
+        }
+    }
+
+    fn tick(&self) {
+        let tick = self.inner.channel.borrow_mut().tick(now());
+        display_latency(self.inner.channel.borrow().heartbeat());
+        self.run_replies(tick.replies);
+        if tick.dead {
+            let missed = self.inner.channel.borrow().heartbeat().missed();
+            warn!("No pong for {} pings, reconnecting", missed);
+            // A half-open socket may never report that it closed
+            self.detach_socket();
+            self.schedule_reconnect();
+        }
+    }
+
+    fn schedule_reconnect(&self) {
+        self.stop_ticking();
+        let delay = self.inner.backoff.borrow_mut().next_delay();
+        log!("Reconnecting in {}ms", delay);
+        self.set_state(ConnectionState::Reconnecting);
+
+        let callbacks = self.inner.callbacks.borrow();
+        let timer = set_timeout(
+            &callbacks.as_ref().expect("Callbacks not initialized").reconnect,
+            delay
+        );
+        self.inner.reconnect_timer.set(Some(timer));
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        self.inner.state.set(state);
+        display_state(state);
+    }
+}
+
+impl Transport for Connection {
+    fn channel(&self) -> &RefCell<Channel> {
+        &self.inner.channel
+    }
+
+    fn now(&self) -> f64 {
+        now()
+    }
+
+    fn on_reply(&self, reply: &Reply) {
+        match reply.outcome {
+            Outcome::TimedOut => warn!("No reply to {} request", reply.name),
+            Outcome::Disconnected => {
+                warn!("Connection lost before a reply to {} request", reply.name);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Number of messages waiting to be sent, for diagnostics.
+#[wasm_bindgen]
+pub fn outbound_queue_depth() -> usize {
+    Connection::active().map_or(0, |connection| connection.queue_len())
+}
+
+fn display_state(state: ConnectionState) {
+    let status = get_element_by_id("connection-status");
+    status.set_text_content(Some(state.label()));
+    status.set_class_name(state.class_name());
+}
+
+fn display_latency(heartbeat: &Heartbeat) {
+    let latency = get_element_by_id("latency");
+    match (heartbeat.latency_ms(), heartbeat.quality()) {
+        (Some(ms), Some(quality)) => {
+            latency.set_text_content(Some(&format!("{:.0} ms", ms)));
+            latency.set_class_name(quality.class_name());
+        }
+        _ => {
+            latency.set_class_name("hidden");
+        }
+    }
+}
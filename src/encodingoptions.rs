@@ -0,0 +1,19 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::binary::BINARY_VERSION;
+
+/// Message encoding requested by the client and confirmed by the server.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct EncodingOptions {
+    /// Whether moves, board snapshots and player lists are sent as binary frames.
+    pub binary: bool,
+    pub version: u8,
+}
+impl EncodingOptions {
+    pub fn binary() -> Self {
+        Self {
+            binary: true,
+            version: BINARY_VERSION,
+        }
+    }
+}
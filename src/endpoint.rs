@@ -0,0 +1,204 @@
+use std::{ cell::RefCell, fmt };
+
+use wasm_bindgen::prelude::*;
+use web_sys::UrlSearchParams;
+
+use crate::{ connection::Connection, error, log, utils::{ document, get_element_by_id, window } };
+
+/// Port used when no endpoint is configured.
+pub const DEFAULT_PORT: u16 = 9001;
+/// Query parameter that overrides the server endpoint, e.g. `?server=wss://example.com/ws`.
+pub const QUERY_PARAMETER: &str = "server";
+/// `<meta name="tictactoe-server" content="...">` tag that overrides the server endpoint.
+pub const META_NAME: &str = "tictactoe-server";
+
+thread_local! {
+    static CONFIGURED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EndpointError {
+    UnsupportedScheme(String),
+    MissingHost,
+    InvalidHost(String),
+    InvalidPort(String),
+}
+impl fmt::Display for EndpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EndpointError::UnsupportedScheme(scheme) =>
+                write!(f, "unsupported scheme \"{}\", expected ws or wss", scheme),
+            EndpointError::MissingHost => write!(f, "missing host"),
+            EndpointError::InvalidHost(host) => write!(f, "invalid host \"{}\"", host),
+            EndpointError::InvalidPort(port) => write!(f, "invalid port \"{}\"", port),
+        }
+    }
+}
+
+/// Picks `wss` for pages served over https and `ws` otherwise.
+pub fn scheme_for(page_protocol: &str) -> &'static str {
+    if page_protocol.trim_end_matches(':').eq_ignore_ascii_case("https") { "wss" } else { "ws" }
+}
+
+/// Resolves a configured endpoint into a full websocket URL.
+///
+/// The endpoint may be a full `ws://`/`wss://` URL, a `host[:port][/path]`
+/// that gets its scheme from the page, or a `/path` on the page's own host.
+/// `None` selects the default server on port 9001 of the page's host.
+///
+/// `page_host` is the page's `location.host`, including the port if any.
+pub fn resolve(
+    endpoint: Option<&str>,
+    page_protocol: &str,
+    page_host: &str
+) -> Result<String, EndpointError> {
+    let scheme = scheme_for(page_protocol);
+
+    let endpoint = match endpoint.map(str::trim) {
+        None => {
+            let hostname = if page_host.ends_with(']') {
+                page_host
+            } else {
+                page_host.rsplit_once(':').map_or(page_host, |(hostname, _)| hostname)
+            };
+            return validate(&format!("{}://{}:{}/", scheme, hostname, DEFAULT_PORT));
+        }
+        Some(endpoint) => endpoint,
+    };
+
+    if endpoint.starts_with('/') {
+        validate(&format!("{}://{}{}", scheme, page_host, endpoint))
+    } else if endpoint.contains("://") {
+        validate(endpoint)
+    } else {
+        validate(&format!("{}://{}", scheme, endpoint))
+    }
+}
+
+/// Checks that `url` is a well-formed websocket URL and normalizes it.
+pub fn validate(url: &str) -> Result<String, EndpointError> {
+    let (scheme, rest) = url.split_once("://").ok_or(EndpointError::MissingHost)?;
+    let scheme = scheme.to_ascii_lowercase();
+    if scheme != "ws" && scheme != "wss" {
+        return Err(EndpointError::UnsupportedScheme(scheme));
+    }
+
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+
+    let (host, port) = if authority.starts_with('[') {
+        // IPv6 literal
+        let end = authority
+            .find(']')
+            .ok_or_else(|| EndpointError::InvalidHost(authority.to_string()))?;
+        let port = authority[end + 1..].strip_prefix(':');
+        if port.is_none() && end + 1 != authority.len() {
+            return Err(EndpointError::InvalidHost(authority.to_string()));
+        }
+        (&authority[..=end], port)
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+
+    if host.is_empty() {
+        return Err(EndpointError::MissingHost);
+    }
+    let valid_host = if host.starts_with('[') {
+        host[1..host.len() - 1].chars().all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.')
+    } else {
+        host.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    };
+    if !valid_host {
+        return Err(EndpointError::InvalidHost(host.to_string()));
+    }
+
+    if let Some(port) = port {
+        match port.parse::<u16>() {
+            Ok(p) if p != 0 => {}
+            _ => {
+                return Err(EndpointError::InvalidPort(port.to_string()));
+            }
+        }
+    }
+
+    let path = if path.is_empty() { "/" } else { path };
+    Ok(format!("{}://{}{}", scheme, authority, path))
+}
+
+/// Returns the URL of the server to connect to.
+///
+/// Sources in order of priority: `set_server_url`, the `server` query
+/// parameter, the `tictactoe-server` meta tag. Falls back to the default
+/// server if none is set or the configured one is malformed. `None` when
+/// there is no usable address at all, e.g. on a `file://` page.
+pub(crate) fn server_url() -> Option<String> {
+    let location = window().location();
+    let protocol = location.protocol().unwrap_or_default();
+    let host = location.host().unwrap_or_default();
+
+    let configured = CONFIGURED.with(|c| c.borrow().clone())
+        .or_else(query_parameter)
+        .or_else(meta_tag);
+
+    match resolve(configured.as_deref(), &protocol, &host) {
+        Ok(url) => {
+            hide_error();
+            Some(url)
+        }
+        Err(err) => {
+            let message = match &configured {
+                Some(endpoint) => format!("Invalid server address \"{}\": {}", endpoint, err),
+                None => format!("No server address for this page: {}", err),
+            };
+            error!("{}", message);
+            display_error(&message);
+            configured.and_then(|_| resolve(None, &protocol, &host).ok())
+        }
+    }
+}
+
+/// Sets the server endpoint and reconnects to it.
+#[wasm_bindgen]
+pub fn set_server_url(url: &str) -> Result<(), JsValue> {
+    let location = window().location();
+    let resolved = resolve(
+        Some(url),
+        &location.protocol().unwrap_or_default(),
+        &location.host().unwrap_or_default()
+    ).map_err(|err| JsValue::from_str(&format!("Invalid server address \"{}\": {}", url, err)))?;
+
+    log!("Server address set to {}", resolved);
+    CONFIGURED.with(|c| {
+        *c.borrow_mut() = Some(url.to_string());
+    });
+
+    if let Some(connection) = Connection::active() {
+        connection.set_url(&resolved);
+    }
+    Ok(())
+}
+
+fn query_parameter() -> Option<String> {
+    let search = window().location().search().ok()?;
+    UrlSearchParams::new_with_str(&search).ok()?.get(QUERY_PARAMETER)
+}
+
+fn meta_tag() -> Option<String> {
+    document()
+        .query_selector(&format!("meta[name=\"{}\"]", META_NAME))
+        .ok()??
+        .get_attribute("content")
+}
+
+fn display_error(message: &str) {
+    let element = get_element_by_id("connection-error");
+    element.set_text_content(Some(message));
+    element.set_class_name("");
+}
+
+fn hide_error() {
+    get_element_by_id("connection-error").set_class_name("hidden");
+}
@@ -1,5 +1,5 @@
 use wasm_bindgen::prelude::*;
-use web_sys::{ HtmlCanvasElement, WebGlBuffer, MouseEvent, WheelEvent, WebSocket };
+use web_sys::{ HtmlCanvasElement, WebGlBuffer, MouseEvent, WheelEvent };
 use web_sys::{ HtmlImageElement, WebGl2RenderingContext, WebGlShader, WebGlProgram };
 use std::cell::RefCell;
 use std::collections::VecDeque;
@@ -10,11 +10,13 @@ use std::rc::Rc;
 use webgl_matrix::{ Matrix, ProjectionMatrix, Mat4, MulVectorMatrix };
 use crate::gameobject::GameObject;
 use crate::grid::Grid;
+use crate::history::{ History, Step };
 use crate::mouse::{ MouseTracker, FloatPos };
-use crate::net::send;
-use crate::player::Player;
-use crate::playermove::PlayerMove;
+use crate::notifications::{ notify, notify_failure, Level };
+use crate::protocol::ClientEvent;
+use crate::session::Session;
 use crate::texture::Texture;
+use crate::transport::Transport;
 use crate::utils::{ now, Size };
 pub use crate::log;
 
@@ -24,25 +26,34 @@ pub struct Game {
     shader_program: WebGlProgram,
     tile_scale: f32,
     aspect_ratio: f32,
-    grid: Grid,
     view_matrix: Mat4,
     projection_matrix: Mat4,
     last_time: f64,
-    texture_indices: Vec<usize>,
     frame_times: VecDeque<f64>,
     model_buffer: Option<WebGlBuffer>,
     hover_tile: Option<Size>,
     mouse_tracker: MouseTracker,
-    ws: WebSocket,
-    players: Rc<RefCell<Vec<Player>>>,
+    transport: Rc<dyn Transport>,
+    session: Rc<RefCell<Session>>,
     textures: Vec<Texture>,
+    /// `None` when the game was already running, e.g. after a reconnect.
+    started_at: Option<f64>,
+    /// Set when the game is over, stops input and highlights the winning tiles.
+    finished: bool,
+    highlighted: Vec<Size>,
+    /// Whether it is the local player's turn, updated every frame.
+    can_move: bool,
+    /// Local player's texture id and tile tint, updated every frame.
+    own_tiles: Option<(i32, [f32; 4])>,
+    history: History,
+    /// Board at the ply shown, `None` while live.
+    snapshot: Option<Rc<Grid>>,
 }
 impl Game {
     pub(crate) fn new(
         canvas_id: &str,
-        grid: Grid,
-        ws: &WebSocket,
-        players: &Rc<RefCell<Vec<Player>>>
+        session: &Rc<RefCell<Session>>,
+        transport: &Rc<dyn Transport>
     ) -> Game {
         let document = web_sys::window().unwrap().document().unwrap();
         let canvas = document.get_element_by_id(canvas_id).unwrap();
@@ -59,22 +70,27 @@ impl Game {
             shader_program: shader_program,
             tile_scale: 0.8,
             aspect_ratio: 1.0,
-            grid: grid,
             view_matrix: Mat4::identity(),
             projection_matrix: Mat4::create_perspective(PI / 2.0, 1.0, 0.1, 100.0),
             last_time: now(),
-            texture_indices: Vec::new(),
             frame_times: VecDeque::new(),
             model_buffer: None,
             hover_tile: None,
             mouse_tracker: MouseTracker::new(),
-            ws: ws.clone(),
-            players: players.clone(),
+            transport: transport.clone(),
+            session: session.clone(),
             textures: Vec::from([
                 Texture::from_url(-1, "empty.png"),
                 Texture::from_url(-2, "blocked.png"),
                 Texture::from_url(-3, "unknown.png"),
             ]),
+            started_at: if session.borrow().at_game_start() { Some(now()) } else { None },
+            finished: false,
+            highlighted: Vec::new(),
+            can_move: true,
+            own_tiles: None,
+            history: History::new(),
+            snapshot: None,
         };
 
         instance.init();
@@ -130,24 +146,6 @@ impl Game {
 
     pub fn init(&mut self) {
         self.view_matrix[14] = -2.0; // Default zoom
-        self.init_texture_indices();
-    }
-
-    fn init_texture_indices(&mut self) {
-        self.texture_indices = Vec::with_capacity((self.grid.size.x * self.grid.size.y) as usize);
-
-        // Y
-        for i in 0..self.grid.size.y {
-            // X
-            for j in 0..self.grid.size.x {
-                let index = self.grid.get_pos(&Size::new(j, i));
-                if index.is_some() {
-                    self.texture_indices.push((index.unwrap() + 1) as usize);
-                } else {
-                    self.texture_indices.push(0);
-                }
-            }
-        }
     }
 
     pub fn create_shader(
@@ -439,23 +437,39 @@ impl Game {
     }
 
     pub fn draw_grid(&mut self) {
+        let session = self.session.clone();
+        let mut session = session.borrow_mut();
+
         {
             // Setup textures
-            for player in self.players.borrow_mut().iter_mut() {
+            for player in session.players.iter_mut() {
                 let index = self.textures
                     .iter()
-                    .position(|p| p.id == player.id.try_into().unwrap());
+                    .position(|p| p.id == i32::try_from(player.id).unwrap());
                 if index != None {
-                    self.textures[index.unwrap()].image = player.get_image(&self.ws);
+                    self.textures[index.unwrap()].image = player.get_image(
+                        self.transport.as_ref()
+                    );
                     continue;
                 }
-                let image = player.get_image(&self.ws);
+                let image = player.get_image(self.transport.as_ref());
                 self.textures.push(Texture::new(player.id.try_into().unwrap(), image));
             }
         }
 
-        let width: f32 = i32::try_from(self.grid.size.x).unwrap() as f32;
-        let height: f32 = i32::try_from(self.grid.size.y).unwrap() as f32;
+        self.can_move = session.can_move() && self.history.is_live();
+        self.own_tiles = session.player_id().map(|id| {
+            let [r, g, b] = session.settings.symbol_rgb();
+            (i32::try_from(id).unwrap(), [r, g, b, 0.5])
+        });
+        let snapshot = self.snapshot.clone();
+        let grid = match &snapshot {
+            Some(grid) => grid.as_ref(),
+            None => session.board.as_ref().expect("Game started without a board"),
+        };
+
+        let width: f32 = i32::try_from(grid.size.x).unwrap() as f32;
+        let height: f32 = i32::try_from(grid.size.y).unwrap() as f32;
 
         let tile_width: f32 = 1.0 / width;
         let tile_height: f32 = 1.0 / height;
@@ -485,19 +499,19 @@ impl Game {
             );
         }
 
-        let scale = if self.grid.size.x > self.grid.size.y {
-            1.0 / (self.grid.size.x as f32)
+        let scale = if grid.size.x > grid.size.y {
+            1.0 / (grid.size.x as f32)
         } else {
-            1.0 / (self.grid.size.y as f32)
+            1.0 / (grid.size.y as f32)
         };
         let tile_size = if tile_width > tile_height { tile_height } else { tile_width };
 
         self.hover_tile = None;
 
         // Y
-        for i in 0..self.grid.size.y {
+        for i in 0..grid.size.y {
             // X
-            for j in 0..self.grid.size.x {
+            for j in 0..grid.size.x {
                 let origin_x = Game::convert_x_to_screen((j as f32) * tile_size + tile_size / 2.0);
                 let origin_y = Game::convert_y_to_screen((i as f32) * tile_size + tile_size / 2.0);
 
@@ -530,9 +544,9 @@ impl Game {
                     &[1.0, 0.0, 0.0]
                 );
 
-                let tile_colors = self.get_tile_colors(j, i, &model_matrix);
+                let texture_id = grid.get_pos(&Size::new(j, i));
 
-                let texture_id = self.grid.get_pos(&Size::new(j, i));
+                let tile_colors = self.get_tile_colors(j, i, texture_id, &model_matrix);
 
                 game_objects.push(
                     GameObject::new_tile(model_matrix, tile_colors.to_vec(), if
@@ -629,12 +643,19 @@ impl Game {
         y * -2.0 + 1.0
     }
 
-    fn get_tile_colors(&mut self, x: i32, y: i32, model_matrix: &Mat4) -> [f32; 16] {
+    fn get_tile_colors(
+        &mut self,
+        x: i32,
+        y: i32,
+        texture_id: Option<i32>,
+        model_matrix: &Mat4
+    ) -> [f32; 16] {
         let mut lt_color: [f32; 4] = [1.0, 0.0, 0.0, 0.1];
         let mut lb_color: [f32; 4] = [0.0, 1.0, 0.0, 0.1];
         let mut rt_color: [f32; 4] = [0.0, 0.0, 1.0, 0.1];
         let mut rb_color: [f32; 4] = [0.0, 0.0, 0.0, 0.1];
 
+        // Tracked even when we can't move, so a click can say why
         if self.hover_tile == None {
             let screen_pos = self.get_tile_pos_on_screen(&model_matrix);
             if
@@ -653,11 +674,21 @@ impl Game {
             }
         }
 
-        if self.hover_tile == Some(Size::new(x, y)) {
+        if self.can_move && self.hover_tile == Some(Size::new(x, y)) {
             lt_color = [1.0, 1.0, 1.0, 0.8];
             lb_color = [1.0, 1.0, 1.0, 0.8];
             rt_color = [1.0, 1.0, 1.0, 0.8];
             rb_color = [1.0, 1.0, 1.0, 0.8];
+        } else if self.highlighted.contains(&Size::new(x, y)) {
+            lt_color = [1.0, 0.8, 0.0, 0.9];
+            lb_color = [1.0, 0.8, 0.0, 0.9];
+            rt_color = [1.0, 0.8, 0.0, 0.9];
+            rb_color = [1.0, 0.8, 0.0, 0.9];
+        } else if let Some((_, color)) = self.own_tiles.filter(|(id, _)| Some(*id) == texture_id) {
+            lt_color = color;
+            lb_color = color;
+            rt_color = color;
+            rb_color = color;
         }
 
         let mut result: [f32; 16] = [0.0; 16];
@@ -734,7 +765,7 @@ impl Game {
         e.prevent_default();
         e.stop_propagation();
     }
-    pub fn on_mouse_up(&mut self, e: MouseEvent, ws: &WebSocket) {
+    pub fn on_mouse_up(&mut self, e: MouseEvent) {
         log!("mouseup {:?}", e.button());
         self.update_mouse_pos(&e);
         match e.button() {
@@ -753,9 +784,23 @@ impl Game {
                         log!("Clicked on {:?}", self.hover_tile);
                         if self.hover_tile.is_some() {
                             let pos = self.hover_tile.unwrap();
-                            if self.grid.is_valid_move(&pos) {
-                                send(ws, "move", self.hover_tile.unwrap().to_json().as_str());
-                                log!("Sent move");
+                            if !self.history.is_live() {
+                                notify(Level::Info, "Go back to live to play");
+                                return;
+                            }
+                            let validation = {
+                                let session = self.session.borrow();
+                                session.validate_move(session.player_id(), &pos)
+                            };
+                            match validation {
+                                Ok(()) => {
+                                    self.transport.request(
+                                        &ClientEvent::Move(pos),
+                                        notify_failure("Move")
+                                    );
+                                    log!("Sent move");
+                                }
+                                Err(err) => notify(Level::Warning, &err.to_string()),
                             }
                         }
                     }
@@ -796,9 +841,38 @@ impl Game {
             )
         );
     }
-    pub fn add_move(&mut self, m: PlayerMove) {
-        self.grid.add(m);
-        self.init_texture_indices();
+    /// Moves through the game's history, new moves keep arriving meanwhile.
+    pub fn navigate(&mut self, step: Step) {
+        let session = self.session.clone();
+        let session = session.borrow();
+        let grid = session.board.as_ref().expect("Game started without a board");
+        let total = grid.moves().len();
+        self.history.step(step, total);
+        self.snapshot = if self.history.is_live() {
+            None
+        } else {
+            Some(Rc::new(grid.at_ply(self.history.ply(total))))
+        };
+        self.hover_tile = None;
+    }
+    /// Moves shown and moves played so far.
+    pub fn history_position(&self) -> (usize, usize) {
+        let session = self.session.borrow();
+        let total = session.board.as_ref().map_or(0, |grid| grid.moves().len());
+        (self.history.ply(total), total)
+    }
+    /// Freezes the board once the game is over.
+    pub fn finish(&mut self, winning_cells: &[Size]) {
+        self.finished = true;
+        self.hover_tile = None;
+        self.highlighted = winning_cells.to_vec();
+    }
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    /// Time since the game started, if this client saw it start.
+    pub fn elapsed_ms(&self) -> Option<f64> {
+        self.started_at.map(|started_at| now() - started_at)
     }
 }
 
@@ -1,7 +1,7 @@
 use serde::Deserialize;
 
-#[derive(Deserialize, Clone)]
-pub(crate) struct GameInfo {
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct GameInfo {
     pub id: u32,
     pub player_list: Vec<u32>,
     pub creator: u32,
@@ -13,3 +13,19 @@ pub(crate) struct GameInfo {
     pub width: u32,
     pub height: u32,
 }
+impl GameInfo {
+    /// Player whose turn it was when this info was sent.
+    ///
+    /// `current_turn` counts turns, so it wraps around `player_list`.
+    pub fn current_player(&self) -> Option<u32> {
+        if self.player_list.is_empty() {
+            return None;
+        }
+        Some(self.player_list[(self.current_turn as usize) % self.player_list.len()])
+    }
+    /// Player moving after `player`, in `player_list` order.
+    pub fn next_player(&self, player: u32) -> Option<u32> {
+        let index = self.player_list.iter().position(|&p| p == player)?;
+        Some(self.player_list[(index + 1) % self.player_list.len()])
+    }
+}
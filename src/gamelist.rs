@@ -0,0 +1,80 @@
+use crate::gameinfo::GameInfo;
+
+/// Views of the game list entries keyed by game id, with the info each was rendered from.
+///
+/// `sync` only touches entries whose info changed or whose place in the list did. New
+/// and moved entries are inserted before the entry that follows them in the new list.
+pub struct GameList<V> {
+    entries: Vec<(GameInfo, V)>,
+}
+impl<V> Default for GameList<V> {
+    fn default() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+impl<V> GameList<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Brings the views in line with `games`.
+    ///
+    /// `create` and `place` get the view the entry goes before, `None` to append it.
+    /// Entries the server reordered are moved with `place`.
+    pub fn sync(
+        &mut self,
+        games: &[GameInfo],
+        mut create: impl FnMut(&GameInfo, Option<&V>) -> V,
+        mut update: impl FnMut(&GameInfo, &mut V),
+        mut place: impl FnMut(&V, Option<&V>),
+        mut remove: impl FnMut(V)
+    ) {
+        let (kept, removed): (Vec<_>, Vec<_>) = self.entries
+            .drain(..)
+            .partition(|(info, _)| games.iter().any(|g| g.id == info.id));
+        removed.into_iter().for_each(|(_, view)| remove(view));
+
+        let mut kept: Vec<Option<(GameInfo, V)>> = kept.into_iter().map(Some).collect();
+        let mut entries: Vec<(GameInfo, V)> = Vec::with_capacity(games.len());
+        // Old position of the closest following entry that stayed in place
+        let mut bound = kept.len();
+        // Backwards, so the entry following a new or moved one is already in place
+        for game in games.iter().rev() {
+            let existing = kept
+                .iter()
+                .position(|entry| entry.as_ref().is_some_and(|(info, _)| info.id == game.id))
+                .and_then(|index| Some((index, kept[index].take()?)));
+            let next = entries.last().map(|(_, view)| view);
+            let entry = match existing {
+                Some((index, (info, mut view))) => {
+                    if index < bound {
+                        bound = index;
+                    } else {
+                        place(&view, next);
+                    }
+                    if info != *game {
+                        update(game, &mut view);
+                    }
+                    (game.clone(), view)
+                }
+                None => (game.clone(), create(game, next)),
+            };
+            entries.push(entry);
+        }
+        entries.reverse();
+        self.entries = entries;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn view(&self, id: u32) -> Option<&V> {
+        self.entries
+            .iter()
+            .find(|(info, _)| info.id == id)
+            .map(|(_, view)| view)
+    }
+}
@@ -1,10 +1,9 @@
-use js_sys::JSON;
-use serde::Serialize;
+use serde::{ Deserialize, Serialize };
 
-use crate::utils::Size;
+use crate::{ gameinfo::GameInfo, utils::Size };
 
-#[derive(Serialize)]
-pub(crate) struct GameParameters {
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameParameters {
     pub size: Size,
     pub hotjoin: bool,
     pub player_limit: u32,
@@ -19,10 +18,13 @@ impl GameParameters {
             length_to_win: length_to_win,
         }
     }
-    pub fn to_json(&self) -> String {
-        JSON::stringify(&serde_wasm_bindgen::to_value(&self).expect("Unable to serialize"))
-            .expect("Unable to stringify")
-            .as_string()
-            .expect("Not string")
+    /// Parameters the game was created with.
+    pub fn from_info(info: &GameInfo) -> Self {
+        Self::new(
+            Size::new(info.width as i32, info.height as i32),
+            info.hotjoin,
+            info.player_limit,
+            info.win_length
+        )
     }
 }
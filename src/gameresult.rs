@@ -0,0 +1,31 @@
+use serde::Deserialize;
+
+use crate::utils::Size;
+
+/// Content of the `game_over` event.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct GameResult {
+    /// Id of the winning player, `None` on a draw.
+    pub winner: Option<u32>,
+    /// Tiles forming the winning line.
+    #[serde(default)]
+    pub winning_cells: Vec<Size>,
+    #[serde(default)]
+    pub draw: bool,
+}
+impl GameResult {
+    pub fn is_draw(&self) -> bool {
+        self.draw || self.winner.is_none()
+    }
+}
+
+/// Formats a duration as `m:ss`, or `h:mm:ss` for games over an hour.
+pub fn format_duration(ms: f64) -> String {
+    let seconds = (ms.max(0.0) / 1000.0).round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+/// Content of the `watching` and `viewers` events.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct GameViewers {
+    pub id: u32,
+    /// Number of spectators currently watching the game.
+    pub viewers: u32,
+}
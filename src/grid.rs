@@ -1,85 +1,169 @@
-use std::convert::TryInto;
+use std::fmt;
 
 use serde::Deserialize;
 
-use crate::{ utils::{ Size, from_json }, playermove::PlayerMove };
+use crate::{ moveerror::MoveError, utils::Size, playermove::PlayerMove };
 
-#[derive(Deserialize, Debug)]
+/// Largest width and height of a board the client creates or loads.
+pub const MAX_SIZE: i32 = 100;
+
+/// Directions a winning line can run in: horizontal, vertical and both diagonals.
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+/// A line of at least `win_length` tiles owned by one player.
+#[derive(Debug, PartialEq)]
+pub struct Win {
+    pub player: i32,
+    /// Every tile of the line, in order.
+    pub cells: Vec<Size>,
+}
+
+/// Board as sent by the server, only its moves.
+#[derive(Deserialize)]
+struct GridData {
+    size: Size,
+    moves: Vec<PlayerMove>,
+}
+
+/// Game board: the owner of every tile, and the moves that led there.
+#[derive(Deserialize)]
+#[serde(from = "GridData")]
 pub struct Grid {
     pub size: Size,
-    pub moves: Vec<PlayerMove>,
-    #[serde(skip_deserializing)]
-    cache: Vec<Option<i32>>,
+    /// Every move in the order it was played.
+    moves: Vec<PlayerMove>,
+    /// Owner of each tile, row by row.
+    cells: Vec<Option<i32>>,
 }
-impl Grid {
-    pub fn from_json(text: &str) -> Result<Self, String> {
-        from_json(text)
+impl From<GridData> for Grid {
+    fn from(data: GridData) -> Self {
+        Grid::new(data.size, data.moves)
     }
-    /// Returns None if the tile is empty, otherwise returns the player's id.
-    pub fn get_pos(&mut self, pos: &Size) -> Option<i32> {
-        self.check_cache_integrity();
-        let cache_result =
-            self.cache
-                [
-                    <i32 as TryInto<usize>>
-                        ::try_into(pos.y * self.size.x + pos.x)
-                        .expect("Could not convert to usize")
-                ];
-        if cache_result.is_some() {
-            return cache_result;
-        }
-
-        let index = self.get_index(pos);
-
-        if index.is_some() {
-            let value = self.moves[index.unwrap()].player;
-            self.update_cache(pos.x, pos.y, value);
-            return Some(value);
+}
+impl fmt::Debug for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Grid")
+            .field("size", &self.size)
+            .field("moves", &self.moves.len())
+            .finish()
+    }
+}
+impl Grid {
+    pub fn new(size: Size, moves: Vec<PlayerMove>) -> Self {
+        let area = (size.x.max(0) as usize)
+            .checked_mul(size.y.max(0) as usize)
+            .expect("Board too large");
+        let mut grid = Self {
+            size,
+            moves: Vec::with_capacity(moves.len()),
+            cells: vec![None; area],
+        };
+        for m in moves {
+            grid.add(m);
         }
-        None
+        grid
+    }
+    /// Returns None if the tile is empty or outside the board, otherwise the player's id.
+    pub fn get_pos(&self, pos: &Size) -> Option<i32> {
+        self.index(pos).and_then(|index| self.cells[index])
     }
+    /// Plays a move, the last move on a tile owns it.
+    ///
+    /// Moves outside the board are only logged.
     pub fn add(&mut self, m: PlayerMove) {
-        self.check_cache_integrity();
-        self.update_cache(m.position.x, m.position.y, m.player);
+        if let Some(index) = self.index(&m.position) {
+            self.cells[index] = Some(m.player);
+        }
         self.moves.push(m);
     }
-    fn get_index(&mut self, pos: &Size) -> Option<usize> {
-        let index = self.moves
-            .iter()
-            .rev()
-            .position(|m| m.position == *pos);
-        if index.is_none() {
-            return None;
-        }
-        Some(self.moves.len() - index.unwrap() - 1)
+    pub fn moves(&self) -> &[PlayerMove] {
+        &self.moves
+    }
+    /// The board as it stood after the first `ply` moves.
+    pub fn at_ply(&self, ply: usize) -> Grid {
+        let moves = self.moves[..ply.min(self.moves.len())].to_vec();
+        Grid::new(self.size, moves)
     }
-    pub fn is_empty(&mut self, pos: &Size) -> bool {
+    pub fn is_empty(&self, pos: &Size) -> bool {
         self.get_pos(pos).is_none()
     }
-    pub fn is_valid_move(&mut self, pos: &Size) -> bool {
-        self.is_empty(pos) && pos.x < self.size.x && pos.y < self.size.y
+    pub fn is_valid_move(&self, pos: &Size) -> bool {
+        self.check_position(pos).is_ok()
+    }
+    /// Checks the tile can be played, whoever's turn it is.
+    pub fn check_position(&self, pos: &Size) -> Result<(), MoveError> {
+        if !self.contains(pos) {
+            return Err(MoveError::OutOfBounds(*pos));
+        }
+        if !self.is_empty(pos) {
+            return Err(MoveError::Occupied(*pos));
+        }
+        Ok(())
     }
-    fn check_cache_integrity(&mut self) {
-        if
-            self.cache.len() !=
-            (self.size.x * self.size.y).try_into().expect("Could not convert to usize")
-        {
-            self.init_cache();
+    pub fn contains(&self, pos: &Size) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.size.x && pos.y < self.size.y
+    }
+    /// Looks for a winning line anywhere on the board.
+    ///
+    /// Only runs starting at a played tile are followed, so this is linear in the moves.
+    pub fn check_win(&self, win_length: u32) -> Option<Win> {
+        if win_length == 0 {
+            return None;
+        }
+        for m in &self.moves {
+            let pos = m.position;
+            let player = match self.get_pos(&pos) {
+                Some(player) => player,
+                None => {
+                    continue;
+                }
+            };
+            for (dx, dy) in DIRECTIONS {
+                // Counted from the run's first tile instead
+                if self.get_pos(&Size::new(pos.x - dx, pos.y - dy)) == Some(player) {
+                    continue;
+                }
+                let cells = self.run(pos, (dx, dy), player);
+                if cells.len() >= (win_length as usize) {
+                    return Some(Win { player, cells });
+                }
+            }
         }
+        None
     }
-    fn init_cache(&mut self) {
-        self.cache = Vec::new();
-        for _y in 0..self.size.y {
-            for _x in 0..self.size.x {
-                self.cache.push(None);
+    /// Looks for a winning line through `pos`, enough after a move there.
+    pub fn check_win_at(&self, pos: &Size, win_length: u32) -> Option<Win> {
+        if win_length == 0 {
+            return None;
+        }
+        let player = self.get_pos(pos)?;
+        for (dx, dy) in DIRECTIONS {
+            let mut start = *pos;
+            while self.get_pos(&Size::new(start.x - dx, start.y - dy)) == Some(player) {
+                start = Size::new(start.x - dx, start.y - dy);
+            }
+            let cells = self.run(start, (dx, dy), player);
+            if cells.len() >= (win_length as usize) {
+                return Some(Win { player, cells });
             }
         }
+        None
     }
-    fn update_cache(&mut self, x: i32, y: i32, player: i32) {
-        self.cache[
-            <i32 as TryInto<usize>>
-                ::try_into(y * self.size.x + x)
-                .expect("Could not convert to usize")
-        ] = Some(player);
+    /// Tiles owned by `player` from `start` on in the given direction.
+    fn run(&self, start: Size, (dx, dy): (i32, i32), player: i32) -> Vec<Size> {
+        let mut cells = Vec::new();
+        let mut pos = start;
+        while self.get_pos(&pos) == Some(player) {
+            cells.push(pos);
+            pos = Size::new(pos.x + dx, pos.y + dy);
+        }
+        cells
+    }
+    /// Position of a tile in `cells`.
+    pub fn index(&self, pos: &Size) -> Option<usize> {
+        if !self.contains(pos) {
+            return None;
+        }
+        Some((pos.y as usize) * (self.size.x as usize) + (pos.x as usize))
     }
 }
@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use serde::{ Deserialize, Serialize };
+
+/// Number of round trips averaged for the latency readout.
+const SAMPLES: usize = 5;
+
+/// Payload of `ping` and the matching `pong`, echoed back by the server.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Ping {
+    pub id: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Quality {
+    Good,
+    Fair,
+    Poor,
+}
+impl Quality {
+    pub fn class_name(&self) -> &'static str {
+        match self {
+            Quality::Good => "good",
+            Quality::Fair => "fair",
+            Quality::Poor => "poor",
+        }
+    }
+}
+
+/// Tracks keepalive pings and the round-trip times of their pongs.
+///
+/// Times are in milliseconds from any monotonic clock.
+#[derive(Debug)]
+pub struct Heartbeat {
+    interval_ms: i32,
+    max_missed: u32,
+    next_id: u32,
+    pending: Option<(u32, f64)>,
+    missed: u32,
+    samples: VecDeque<f64>,
+}
+impl Heartbeat {
+    pub fn new(interval_ms: i32, max_missed: u32) -> Self {
+        Self {
+            interval_ms,
+            max_missed,
+            next_id: 0,
+            pending: None,
+            missed: 0,
+            samples: VecDeque::with_capacity(SAMPLES),
+        }
+    }
+    pub fn interval_ms(&self) -> i32 {
+        self.interval_ms
+    }
+    /// Starts the next ping. A previous ping still waiting for its pong counts as missed.
+    pub fn ping(&mut self, now: f64) -> Ping {
+        if self.pending.is_some() {
+            self.missed += 1;
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.pending = Some((id, now));
+        Ping { id }
+    }
+    /// Records a pong and returns its round-trip time. Stale pongs are ignored.
+    pub fn pong(&mut self, pong: &Ping, now: f64) -> Option<f64> {
+        match self.pending {
+            Some((id, sent)) if id == pong.id => {
+                let rtt = now - sent;
+                self.pending = None;
+                self.missed = 0;
+                if self.samples.len() == SAMPLES {
+                    self.samples.pop_front();
+                }
+                self.samples.push_back(rtt);
+                Some(rtt)
+            }
+            _ => None,
+        }
+    }
+    /// Whether too many pongs in a row went missing for the connection to be alive.
+    pub fn is_dead(&self) -> bool {
+        self.missed >= self.max_missed
+    }
+    pub fn missed(&self) -> u32 {
+        self.missed
+    }
+    /// Average of the last few round-trip times.
+    pub fn latency_ms(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<f64>() / (self.samples.len() as f64))
+    }
+    pub fn quality(&self) -> Option<Quality> {
+        let latency = self.latency_ms()?;
+        Some(if self.missed > 0 || latency >= 400.0 {
+            Quality::Poor
+        } else if latency >= 150.0 {
+            Quality::Fair
+        } else {
+            Quality::Good
+        })
+    }
+    /// Forgets all pings, used when a new socket opens.
+    pub fn reset(&mut self) {
+        self.pending = None;
+        self.missed = 0;
+        self.samples.clear();
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{ Deserialize, Serialize };
+
+/// Version of the message protocol implemented by this client.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest server protocol this client can talk to.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// Optional protocol features supported by this client.
+pub const FEATURES: &[&str] = &["binary", "heartbeat", "request_id"];
+
+/// First message sent by both sides after the socket opens.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Hello {
+    /// Crate version, only used for display.
+    pub version: String,
+    pub protocol: u32,
+    /// Oldest protocol version of the other side that is still supported.
+    pub min_protocol: u32,
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Id the server assigned to this client, only sent by the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<u32>,
+}
+
+/// Result of comparing the client's and the server's `Hello`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compatibility {
+    Compatible,
+    ClientOutdated,
+    ServerOutdated,
+}
+
+impl Hello {
+    pub fn client() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol: PROTOCOL_VERSION,
+            min_protocol: MIN_PROTOCOL_VERSION,
+            features: FEATURES.iter().map(|f| f.to_string()).collect(),
+            player_id: None,
+        }
+    }
+    /// Checks whether a client sending `self` can talk to a server sending `server`.
+    pub fn compatibility(&self, server: &Hello) -> Compatibility {
+        if self.protocol < server.min_protocol {
+            Compatibility::ClientOutdated
+        } else if server.protocol < self.min_protocol {
+            Compatibility::ServerOutdated
+        } else {
+            Compatibility::Compatible
+        }
+    }
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
@@ -0,0 +1,41 @@
+/// Ways to move through a game's history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    First,
+    Back,
+    Forward,
+    /// Show the board after this many moves.
+    Jump(usize),
+    Live,
+}
+
+/// Which ply of the game is shown, following new moves while live.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct History {
+    /// Moves shown, `None` when live.
+    ply: Option<usize>,
+}
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn is_live(&self) -> bool {
+        self.ply.is_none()
+    }
+    /// Number of moves shown out of `total`.
+    pub fn ply(&self, total: usize) -> usize {
+        self.ply.map_or(total, |ply| ply.min(total))
+    }
+    /// Moves to another ply, reaching the last move goes back to live.
+    pub fn step(&mut self, step: Step, total: usize) {
+        let current = self.ply(total);
+        let target = match step {
+            Step::First => 0,
+            Step::Back => current.saturating_sub(1),
+            Step::Forward => current + 1,
+            Step::Jump(ply) => ply,
+            Step::Live => total,
+        };
+        self.ply = if target >= total { None } else { Some(target) };
+    }
+}
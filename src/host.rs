@@ -0,0 +1,18 @@
+use serde::Serialize;
+
+use crate::gameparameters::GameParameters;
+
+/// Content of `kick_player` and `transfer_host`.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct PlayerAction {
+    pub game_id: u32,
+    /// Player or bot the action applies to.
+    pub player: u32,
+}
+
+/// Content of `update_game`, new parameters for a game that has not started.
+#[derive(Serialize, Clone, Debug)]
+pub struct GameUpdate {
+    pub game_id: u32,
+    pub parameters: GameParameters,
+}
@@ -1,38 +1,81 @@
 mod utils;
+pub mod avatar;
+pub mod binary;
+pub mod chat;
+pub mod connection;
+pub mod endpoint;
 pub mod game;
 mod net;
 mod mouse;
+pub mod moveerror;
 mod player;
 mod gameinfo;
+pub mod gamelist;
 mod gameparameters;
+mod encodingoptions;
+pub mod hello;
+pub mod heartbeat;
+pub mod host;
+pub mod history;
 mod gamejoindata;
-mod gamemessageevent;
-mod grid;
+pub mod gameresult;
+mod gameviewers;
+pub mod grid;
 mod playermove;
 mod playerimageresponse;
+pub mod rematch;
 mod gameobject;
+pub mod session;
+pub mod settings;
+pub mod transport;
+pub mod channel;
+pub mod outboundqueue;
+pub mod pendingrequests;
+mod servererror;
+pub mod notifications;
+pub mod protocol;
+pub mod record;
 mod texture;
 
 use std::{ cell::RefCell, convert::TryInto, rc::Rc };
 
 use gameparameters::GameParameters;
-use net::{ start_websocket, send };
-use utils::{ get_element_by_id, get_elements_by_class_name, set_panic_hook, window, Size };
+use net::{ display_history, handle_event, left_game, start_game, start_websocket };
+use utils::{ get_element_by_id, get_elements_by_class_name, set_panic_hook, Size };
 use wasm_bindgen::prelude::*;
 use web_sys::{
+    Element,
+    FileReader,
     HtmlCanvasElement,
     HtmlElement,
     HtmlSelectElement,
     MouseEvent,
-    WebSocket,
     WheelEvent,
 };
 
 use crate::{
+    avatar::load_avatar,
+    chat::{ prepare_message, ChatRequest, RateLimiter },
     game::Game,
-    gameinfo::GameInfo,
-    player::Player,
-    utils::{ document, get_input_element_by_id },
+    grid::MAX_SIZE,
+    history::Step,
+    notifications::{ notify, notify_failure, Level },
+    pendingrequests::Outcome,
+    protocol::{
+        AvatarUpload,
+        ClientEvent,
+        GameJoinData,
+        GameUpdate,
+        PlayerAction,
+        PlayerImageResponse,
+        ServerEvent,
+    },
+    record::GameRecord,
+    rematch::RematchResponse,
+    session::Session,
+    settings::{ validate_name, PlayerName, Settings },
+    transport::Transport,
+    utils::{ add_event_listener, document, download, get_input_element_by_id, now },
 };
 
 extern crate js_sys;
@@ -48,33 +91,38 @@ pub fn init() {
     let canvas = document().get_element_by_id(canvas_id).unwrap();
     let canvas: HtmlCanvasElement = canvas.dyn_into::<HtmlCanvasElement>().unwrap();
 
-    let current_game: Rc<RefCell<Option<GameInfo>>> = Rc::new(RefCell::new(None));
+    let session: Rc<RefCell<Session>> = Rc::new(RefCell::new(Session::new()));
 
     let game: Rc<RefCell<Option<Game>>> = Rc::new(RefCell::new(None));
 
-    let players: Rc<RefCell<Vec<Player>>> = Rc::new(RefCell::new(Vec::new()));
-
-    let ws = start_websocket(&current_game, &game, &players);
+    let transport: Rc<dyn Transport> = Rc::new(start_websocket(&session, &game));
     //let _ = ws.send_with_str("{\"event\":\"players\",\"content\":\"\"}");
-    update_menu(&ws); // Initial menu update
-
-    register_inputs(&game, &canvas, &ws);
+    update_menu(&transport); // Initial menu update
 
-    start_game_render(&game, &canvas);
+    register_inputs(&game, &canvas);
 
     //Menu should be automatically updated by server on change
     //start_menu_update_timer(&ws);
 
-    register_menu_buttons(&ws);
-    register_lobby_buttons(&ws);
+    register_menu_buttons(&transport);
+    register_lobby_buttons(&transport);
+    register_results_buttons(&game, &session);
+    register_leave_buttons(&game, &session, &transport);
+    register_record_buttons(&game, &session, &transport);
+    register_history_buttons(&game);
+    register_host_buttons(&session, &transport);
+    register_player_actions(&session, &transport);
+    register_rematch_buttons(&session, &transport);
+    register_chat(&session, &transport);
+    register_settings(&session, &game, &transport);
     register_tabs();
 }
 
-fn register_lobby_buttons(ws: &WebSocket) {
-    let ws_clone = ws.clone();
+fn register_lobby_buttons(transport: &Rc<dyn Transport>) {
+    let transport_clone = transport.clone();
     let cb = Closure::wrap(
         Box::new(move || {
-            send(&ws_clone, "ready", "");
+            transport_clone.request(&ClientEvent::Ready, notify_failure("Getting ready"));
         }) as Box<dyn FnMut()>
     );
     get_element_by_id("ready-btn")
@@ -82,22 +130,22 @@ fn register_lobby_buttons(ws: &WebSocket) {
         .expect("Unable to register event");
     cb.forget();
 
-    let ws_clone = ws.clone();
+    let transport_clone = transport.clone();
     let cb = Closure::wrap(
         Box::new(move || {
             let select = get_element_by_id("game-bot-type")
                 .dyn_into::<HtmlSelectElement>()
                 .expect("Not a select element");
 
-            send(
-                &ws_clone,
-                "add_bot",
-                select
-                    .item(select.selected_index().try_into().unwrap())
-                    .expect("No element selected")
-                    .get_attribute("value")
-                    .expect("No value")
-                    .as_str()
+            transport_clone.request(
+                &ClientEvent::AddBot(
+                    select
+                        .item(select.selected_index().try_into().unwrap())
+                        .expect("No element selected")
+                        .get_attribute("value")
+                        .expect("No value")
+                ),
+                notify_failure("Adding bot")
             );
         }) as Box<dyn FnMut()>
     );
@@ -107,35 +155,326 @@ fn register_lobby_buttons(ws: &WebSocket) {
     cb.forget();
 }
 
-fn register_menu_buttons(ws: &WebSocket) {
-    let ws_clone = ws.clone();
+fn register_results_buttons(game: &Rc<RefCell<Option<Game>>>, session: &Rc<RefCell<Session>>) {
+    let game_clone = game.clone();
+    let session_clone = session.clone();
     let cb = Closure::wrap(
         Box::new(move || {
-            let width = get_input_element_by_id("new-game-size-w").value();
-            let height = get_input_element_by_id("new-game-size-h").value();
-            let hotjoin = get_input_element_by_id("new-game-hotjoin").checked();
-            let win_length = get_input_element_by_id("new-game-win-length").value();
+            close_game(&game_clone, &session_clone);
+            get_element_by_id("lobby").set_class_name("fullscreen");
+        }) as Box<dyn FnMut()>
+    );
+    get_element_by_id("results-lobby-btn")
+        .add_event_listener_with_callback("click", cb.as_ref().unchecked_ref())
+        .expect("Unable to register event");
+    cb.forget();
+}
 
-            let width_parsed = width.parse();
-            let height_parsed = height.parse();
-            let win_length_parsed = win_length.parse();
+/// Rematch proposal from the results overlay and the answers to an offer.
+fn register_rematch_buttons(session: &Rc<RefCell<Session>>, transport: &Rc<dyn Transport>) {
+    let session_clone = session.clone();
+    let transport_clone = transport.clone();
+    add_event_listener(&get_element_by_id("results-rematch-btn"), "click", move |_| {
+        let proposal = session_clone.borrow().rematch_proposal();
+        if let Some(proposal) = proposal {
+            transport_clone.request(
+                &ClientEvent::ProposeRematch(proposal),
+                notify_failure("Rematch")
+            );
+        }
+    });
+    for (id, accept) in [("rematch-accept-btn", true), ("rematch-decline-btn", false)] {
+        let session_clone = session.clone();
+        let transport_clone = transport.clone();
+        add_event_listener(&get_element_by_id(id), "click", move |_| {
+            let game_id = session_clone.borrow().rematch.as_ref().map(|r| r.proposal.game_id);
+            if let Some(game_id) = game_id {
+                transport_clone.request(
+                    &ClientEvent::RematchResponse(RematchResponse { game_id, accept }),
+                    notify_failure("Answering rematch")
+                );
+            }
+        });
+    }
+}
+
+/// Export of the current game and import of a saved one as a local replay.
+fn register_record_buttons(
+    game: &Rc<RefCell<Option<Game>>>,
+    session: &Rc<RefCell<Session>>,
+    transport: &Rc<dyn Transport>
+) {
+    for id in ["game-export-btn", "results-export-btn"] {
+        let session_clone = session.clone();
+        add_event_listener(&get_element_by_id(id), "click", move |_| {
+            match session_clone.borrow().record() {
+                Some(record) => {
+                    let name = format!("game-{}.txt", record.game_id.unwrap_or_default());
+                    download(&name, &record.to_string());
+                }
+                None => notify(Level::Warning, "Only games played on the server can be exported"),
+            }
+        });
+    }
 
-            if width_parsed.is_err() || height_parsed.is_err() || win_length_parsed.is_err() {
-                error!("One or more values could not be parsed");
+    let game_clone = game.clone();
+    let session_clone = session.clone();
+    let transport_clone = transport.clone();
+    add_event_listener(&get_element_by_id("import-record"), "change", move |_| {
+        let input = get_input_element_by_id("import-record");
+        let file = match input.files().and_then(|files| files.get(0)) {
+            Some(file) => file,
+            None => {
                 return;
             }
+        };
+        input.set_value("");
+
+        let reader = FileReader::new().expect("Unable to create FileReader");
+        let loaded = reader.clone();
+        let game = game_clone.clone();
+        let session = session_clone.clone();
+        let transport = transport_clone.clone();
+        let on_load = Closure::once_into_js(move || {
+            let text = loaded.result().ok().and_then(|result| result.as_string());
+            let record = match text.map(|text| GameRecord::parse(&text)) {
+                Some(Ok(record)) => record,
+                Some(Err(err)) => {
+                    notify(Level::Error, &format!("Invalid game record: {}", err));
+                    return;
+                }
+                None => {
+                    notify(Level::Error, "Unable to read the game record");
+                    return;
+                }
+            };
+            if let Err(err) = session.borrow_mut().load_record(&record) {
+                notify(Level::Warning, &err);
+                return;
+            }
+            get_element_by_id("menu").set_class_name("fullscreen hidden");
+            start_game(&game, &session, &transport);
+            display_history(&game.borrow());
+        });
+        reader.set_onload(Some(on_load.unchecked_ref()));
+        reader.read_as_text(&file).expect("Unable to read file");
+    });
+}
 
-            send(
-                &ws_clone,
-                "create_game",
-                GameParameters::new(
-                    Size::new(width_parsed.ok().unwrap(), height_parsed.ok().unwrap()),
-                    hotjoin,
-                    100,
-                    win_length_parsed.ok().unwrap()
-                )
-                    .to_json()
-                    .as_str()
+/// Controls stepping through the moves of the game shown.
+fn register_history_buttons(game: &Rc<RefCell<Option<Game>>>) {
+    let navigate = |game: &Rc<RefCell<Option<Game>>>, step: Step| {
+        if let Some(game) = game.borrow_mut().as_mut() {
+            game.navigate(step);
+        }
+        display_history(&game.borrow());
+    };
+    for (id, step) in [
+        ("history-first-btn", Step::First),
+        ("history-back-btn", Step::Back),
+        ("history-forward-btn", Step::Forward),
+        ("history-live-btn", Step::Live),
+    ] {
+        let game_clone = game.clone();
+        add_event_listener(&get_element_by_id(id), "click", move |_| {
+            navigate(&game_clone, step);
+        });
+    }
+    let game_clone = game.clone();
+    add_event_listener(&get_element_by_id("history-slider"), "input", move |_| {
+        if let Ok(ply) = get_input_element_by_id("history-slider").value().parse() {
+            navigate(&game_clone, Step::Jump(ply));
+        }
+    });
+}
+
+/// Buttons leaving the current game, the menu is shown once the server confirms.
+fn register_leave_buttons(
+    game: &Rc<RefCell<Option<Game>>>,
+    session: &Rc<RefCell<Session>>,
+    transport: &Rc<dyn Transport>
+) {
+    for id in ["lobby-leave-btn", "game-leave-btn", "results-menu-btn"] {
+        let game_clone = game.clone();
+        let session_clone = session.clone();
+        let transport_clone = transport.clone();
+        let cb = Closure::wrap(
+            Box::new(move || {
+                // A replay is local, there is nothing to tell the server
+                if session_clone.borrow().replaying {
+                    session_clone.borrow_mut().leave_game();
+                    left_game(&mut game_clone.borrow_mut(), &transport_clone);
+                    return;
+                }
+                transport_clone.request(&ClientEvent::LeaveGame, notify_failure("Leaving game"));
+            }) as Box<dyn FnMut()>
+        );
+        get_element_by_id(id)
+            .add_event_listener_with_callback("click", cb.as_ref().unchecked_ref())
+            .expect("Unable to register event");
+        cb.forget();
+    }
+}
+
+/// Drops the finished game and hides the board and results.
+fn close_game(game: &Rc<RefCell<Option<Game>>>, session: &Rc<RefCell<Session>>) {
+    *game.borrow_mut() = None;
+    session.borrow_mut().end_game();
+    get_element_by_id("results").set_class_name("fullscreen hidden");
+    get_element_by_id("game-container").set_class_name("hidden");
+}
+
+fn register_chat(session: &Rc<RefCell<Session>>, transport: &Rc<dyn Transport>) {
+    let body = get_element_by_id("chat-body");
+    add_event_listener(&get_element_by_id("chat-header"), "click", move |_| {
+        let collapsed = body.class_name() == "hidden";
+        body.set_class_name(if collapsed { "" } else { "hidden" });
+    });
+
+    // 5 messages per 10 seconds
+    let limiter = RefCell::new(RateLimiter::new(5, 10000.0));
+    let session_clone = session.clone();
+    let transport_clone = transport.clone();
+    add_event_listener(&get_element_by_id("chat-form"), "submit", move |e| {
+        e.prevent_default();
+        let game_id = match &session_clone.borrow().current_game {
+            Some(current_game) => current_game.id,
+            None => {
+                return;
+            }
+        };
+        let input = get_input_element_by_id("chat-input");
+        match prepare_message(&input.value(), &mut limiter.borrow_mut(), now()) {
+            Ok(text) => {
+                transport_clone.request(
+                    &ClientEvent::Chat(ChatRequest { game_id, text }),
+                    notify_failure("Sending message")
+                );
+                input.set_value("");
+            }
+            Err(err) => notify(Level::Warning, &err.to_string()),
+        }
+    });
+}
+
+/// Player settings tab: rename through the server, the rest is only stored locally.
+fn register_settings(
+    session: &Rc<RefCell<Session>>,
+    game: &Rc<RefCell<Option<Game>>>,
+    transport: &Rc<dyn Transport>
+) {
+    let settings = Settings::load();
+    apply_settings(&settings);
+    get_input_element_by_id("settings-color").set_value(&settings.symbol_color);
+    get_input_element_by_id("settings-latency").set_checked(settings.show_latency);
+    get_input_element_by_id("settings-chat").set_checked(settings.show_chat);
+    session.borrow_mut().settings = settings;
+
+    let transport_clone = transport.clone();
+    add_event_listener(&get_element_by_id("settings-name-form"), "submit", move |e| {
+        e.prevent_default();
+        match validate_name(&get_input_element_by_id("settings-name").value()) {
+            Ok(name) => {
+                transport_clone.request(
+                    &ClientEvent::SetName(PlayerName { name }),
+                    notify_failure("Renaming")
+                );
+            }
+            Err(err) => notify(Level::Warning, &err.to_string()),
+        }
+    });
+
+    let session_clone = session.clone();
+    let game_clone = game.clone();
+    let transport_clone = transport.clone();
+    add_event_listener(&get_element_by_id("settings-avatar"), "change", move |_| {
+        let input = get_input_element_by_id("settings-avatar");
+        let file = match input.files().and_then(|files| files.get(0)) {
+            Some(file) => file,
+            None => {
+                return;
+            }
+        };
+        input.set_value("");
+        let session = session_clone.clone();
+        let game = game_clone.clone();
+        let transport = transport_clone.clone();
+        load_avatar(&file, move |result| {
+            match result {
+                Ok(image) => upload_avatar(image, &session, &game, &transport),
+                Err(err) => notify(Level::Warning, &err.to_string()),
+            }
+        });
+    });
+
+    for (id, event) in [
+        ("settings-color", "input"),
+        ("settings-latency", "change"),
+        ("settings-chat", "change"),
+    ] {
+        let session_clone = session.clone();
+        add_event_listener(&get_element_by_id(id), event, move |_| {
+            let settings = &mut session_clone.borrow_mut().settings;
+            settings.symbol_color = get_input_element_by_id("settings-color").value();
+            settings.show_latency = get_input_element_by_id("settings-latency").checked();
+            settings.show_chat = get_input_element_by_id("settings-chat").checked();
+            settings.save();
+            apply_settings(settings);
+        });
+    }
+}
+
+/// Shows the new avatar locally once the server took it, others get it as `player_image`.
+/// Without request ids the server's answer is unknown, the image then only
+/// shows if the server sends it to us too.
+fn upload_avatar(
+    image: String,
+    session: &Rc<RefCell<Session>>,
+    game: &Rc<RefCell<Option<Game>>>,
+    transport: &Rc<dyn Transport>
+) {
+    let session = session.clone();
+    let game = game.clone();
+    let transport_clone = transport.clone();
+    let upload = AvatarUpload { image: image.clone() };
+    transport.request(
+        &ClientEvent::SetImage(upload),
+        Box::new(move |outcome| {
+            if outcome != Outcome::Accepted {
+                return notify_failure("Uploading avatar")(outcome);
+            }
+            let id = match session.borrow().player_id() {
+                Some(id) => id,
+                None => {
+                    return;
+                }
+            };
+            let event = ServerEvent::PlayerImage(PlayerImageResponse { id, image });
+            handle_event(event, &session, &game, &transport_clone);
+        })
+    );
+}
+
+fn apply_settings(settings: &Settings) {
+    document()
+        .body()
+        .expect("Missing body")
+        .set_class_name(&settings.body_class());
+}
+
+fn register_menu_buttons(transport: &Rc<dyn Transport>) {
+    let transport_clone = transport.clone();
+    let cb = Closure::wrap(
+        Box::new(move || {
+            let parameters = match read_game_parameters("new-game", 100) {
+                Some(parameters) => parameters,
+                None => {
+                    return;
+                }
+            };
+            transport_clone.request(
+                &ClientEvent::CreateGame(parameters),
+                notify_failure("Creating game")
             );
         }) as Box<dyn FnMut()>
     );
@@ -145,50 +484,108 @@ fn register_menu_buttons(ws: &WebSocket) {
     cb.forget();
 }
 
-fn update_menu(ws: &WebSocket) {
-    log!("Fetching players");
-    send(&ws, "players", "");
-    log!("Fetching games");
-    send(&ws, "games", "");
+/// Reads the `{prefix}-size-w`, `-size-h`, `-hotjoin` and `-win-length` inputs.
+fn read_game_parameters(prefix: &str, player_limit: u32) -> Option<GameParameters> {
+    let input = |name: &str| get_input_element_by_id(&format!("{}-{}", prefix, name));
+    let width = input("size-w").value().parse();
+    let height = input("size-h").value().parse();
+    let win_length = input("win-length").value().parse();
+
+    match (width, height, win_length) {
+        (Ok(width), Ok(height), _) if
+            !(1..=MAX_SIZE).contains(&width) || !(1..=MAX_SIZE).contains(&height)
+        => {
+            notify(Level::Error, &format!("Width and height must be between 1 and {}", MAX_SIZE));
+            None
+        }
+        (Ok(width), Ok(height), Ok(win_length)) =>
+            Some(
+                GameParameters::new(
+                    Size::new(width, height),
+                    input("hotjoin").checked(),
+                    player_limit,
+                    win_length
+                )
+            ),
+        _ => {
+            notify(Level::Error, "Width, height and win length must be numbers");
+            None
+        }
+    }
 }
 
-fn start_game_render(game: &Rc<RefCell<Option<Game>>>, canvas: &HtmlCanvasElement) {
-    let f = Rc::new(RefCell::new(None));
-    let g = f.clone();
+/// Lobby controls only shown to the host, the per-player ones are in the player list.
+fn register_host_buttons(session: &Rc<RefCell<Session>>, transport: &Rc<dyn Transport>) {
+    let session_clone = session.clone();
+    let transport_clone = transport.clone();
+    add_event_listener(&get_element_by_id("host-update-btn"), "click", move |_| {
+        let current_game = match &session_clone.borrow().current_game {
+            Some(current_game) => current_game.clone(),
+            None => {
+                return;
+            }
+        };
+        if let Some(parameters) = read_game_parameters("host", current_game.player_limit) {
+            transport_clone.request(
+                &ClientEvent::UpdateGame(GameUpdate { game_id: current_game.id, parameters }),
+                notify_failure("Updating game")
+            );
+        }
+    });
+
+    let session_clone = session.clone();
+    let transport_clone = transport.clone();
+    add_event_listener(&get_element_by_id("host-start-btn"), "click", move |_| {
+        let game_id = session_clone.borrow().current_game.as_ref().map(|g| g.id);
+        if let Some(game_id) = game_id {
+            transport_clone.request(
+                &ClientEvent::StartGame(GameJoinData::new(game_id)),
+                notify_failure("Starting game")
+            );
+        }
+    });
+}
 
-    let game_clone = game.clone();
-    let canvas_clone = canvas.clone();
-
-    *g.borrow_mut() = Some(
-        Closure::new(move || {
-            if
-                canvas_clone.width() != canvas_clone.client_width().try_into().unwrap() ||
-                canvas_clone.height() != canvas_clone.client_height().try_into().unwrap()
-            {
-                canvas_clone.set_width(canvas_clone.client_width().try_into().unwrap());
-                canvas_clone.set_height(canvas_clone.client_height().try_into().unwrap());
+/// Kick and Make host buttons of the lobby. The player list is rebuilt on
+/// every update, so a single listener on the list handles all of them.
+fn register_player_actions(session: &Rc<RefCell<Session>>, transport: &Rc<dyn Transport>) {
+    let session = session.clone();
+    let transport = transport.clone();
+    add_event_listener(&get_element_by_id("game-player-list"), "click", move |e| {
+        let button = match e.target().and_then(|target| target.dyn_into::<Element>().ok()) {
+            Some(button) => button,
+            None => {
+                return;
             }
-            let mut game_borrowed = game_clone.borrow_mut();
-            if game_borrowed.is_some() {
-                game_borrowed.as_mut().unwrap().render();
+        };
+        let player = button.get_attribute("data-player").and_then(|p| p.parse().ok());
+        let game_id = session.borrow().current_game.as_ref().map(|g| g.id);
+        let action = match (game_id, player) {
+            (Some(game_id), Some(player)) => PlayerAction { game_id, player },
+            _ => {
+                return;
             }
-
-            request_animation_frame(f.borrow().as_ref().unwrap());
-        })
-    );
-
-    request_animation_frame(g.borrow().as_ref().unwrap());
+        };
+        match button.get_attribute("data-action").as_deref() {
+            Some("kick") => {
+                transport.request(&ClientEvent::KickPlayer(action), notify_failure("Kick"));
+            }
+            Some("host") => {
+                transport.request(&ClientEvent::TransferHost(action), notify_failure("Make host"));
+            }
+            _ => {}
+        }
+    });
 }
 
-fn request_animation_frame(f: &Closure<dyn FnMut()>) {
-    window()
-        .request_animation_frame(f.as_ref().unchecked_ref())
-        .expect("should register `requestAnimationFrame` OK");
+fn update_menu(transport: &Rc<dyn Transport>) {
+    log!("Fetching players");
+    transport.send(&ClientEvent::Players);
+    log!("Fetching games");
+    transport.send(&ClientEvent::Games);
 }
 
-fn register_inputs(game: &Rc<RefCell<Option<Game>>>, canvas: &HtmlCanvasElement, ws: &WebSocket) {
-    let ws_clone = ws.clone();
-
+fn register_inputs(game: &Rc<RefCell<Option<Game>>>, canvas: &HtmlCanvasElement) {
     // Mouse move
     let game_clone = game.clone();
     let cb = Closure::wrap(
@@ -229,7 +626,7 @@ fn register_inputs(game: &Rc<RefCell<Option<Game>>>, canvas: &HtmlCanvasElement,
             if game_borrowed.is_none() {
                 return;
             }
-            game_borrowed.as_mut().unwrap().on_mouse_up(e, &ws_clone);
+            game_borrowed.as_mut().unwrap().on_mouse_up(e);
         }) as Box<dyn FnMut(_)>
     );
     canvas
@@ -0,0 +1,28 @@
+use std::fmt;
+
+use crate::utils::Size;
+
+/// Why a move can't be played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveError {
+    /// Not in a game, or its board hasn't arrived yet.
+    NoGame,
+    Spectating,
+    GameOver,
+    /// It is the turn of the given player.
+    NotYourTurn(u32),
+    OutOfBounds(Size),
+    Occupied(Size),
+}
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::NoGame => write!(f, "Not in a game"),
+            MoveError::Spectating => write!(f, "Spectators can't move"),
+            MoveError::GameOver => write!(f, "The game is over"),
+            MoveError::NotYourTurn(_) => write!(f, "It is not your turn"),
+            MoveError::OutOfBounds(pos) => write!(f, "{}x{} is outside the board", pos.x, pos.y),
+            MoveError::Occupied(pos) => write!(f, "{}x{} is already taken", pos.x, pos.y),
+        }
+    }
+}
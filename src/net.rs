@@ -1,150 +1,267 @@
-use std::{ ops::Add, rc::Rc, cell::RefCell };
+use std::{ ops::Add, rc::Rc, cell::{ Cell, RefCell } };
 
-use js_sys::JSON;
 use wasm_bindgen::{ closure::Closure, JsCast };
-use web_sys::{ WebSocket, MessageEvent, ErrorEvent, HtmlElement };
+use web_sys::{ Element, MessageEvent, HtmlCanvasElement, HtmlElement };
 
 use crate::{
+    connection::Connection,
+    endpoint::server_url,
     debug,
     error,
     game::Game,
     gameinfo::GameInfo,
+    gamelist::GameList,
+    gameresult::format_duration,
     gamejoindata::GameJoinData,
-    gamemessageevent::GameMessageEvent,
-    grid::Grid,
+    hello::Compatibility,
     log,
-    player::{ merge_players, set_image, Player },
-    playerimageresponse::PlayerImageResponse,
-    playermove::PlayerMove,
-    utils::{ add_event_listener, document, games_div, get_element_by_id, players_div, set_timeout },
+    notifications::{ failure_message, notify, notify_failure, server_error_message, Level },
+    protocol::{ ClientEvent, ProtocolError, ServerEvent, ServerMessage },
+    session::{ Session, Update },
+    transport::Transport,
+    utils::{
+        document,
+        EventListener,
+        games_div,
+        get_element_by_id,
+        get_input_element_by_id,
+        players_div,
+        window,
+    },
     warn,
 };
 
-pub(crate) fn start_websocket(
-    current_game: &Rc<RefCell<Option<GameInfo>>>,
-    game: &Rc<RefCell<Option<Game>>>,
-    players: &Rc<RefCell<Vec<Player>>>
-) -> WebSocket {
-    let ip = web_sys::window().unwrap().location().hostname().unwrap();
-    let ws = WebSocket::new(format!("ws://{}:9001/", ip).as_str()).unwrap();
-
-    log!("Connecting to {}", format!("wss://{}:9001/", ip));
-
-    ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
-
-    let current_game_clone = current_game.clone();
+thread_local! {
+    /// Whether the render loop is running, it stops once there is no game.
+    static RENDERING: Cell<bool> = const { Cell::new(false) };
+    /// Entries of `#game-list`, kept in sync with the session's games.
+    static GAME_LIST: RefCell<GameList<GameEntry>> = RefCell::new(GameList::new());
+}
 
-    let mut game_list: Rc<RefCell<Vec<GameInfo>>> = Rc::new(RefCell::new(Vec::new()));
+pub(crate) fn start_websocket(
+    session: &Rc<RefCell<Session>>,
+    game: &Rc<RefCell<Option<Game>>>
+) -> Connection {
+    let session_clone = session.clone();
     let game_clone = game.clone();
-    let players_clone = players.clone();
 
-    let ws_clone = ws.clone();
-    let onmessage_callback = Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| {
+    let on_message = move |connection: &Connection, e: MessageEvent| {
         // Handle difference Text/Binary,...
-        if let Ok(abuf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
-            log!("message event, received arraybuffer: {:?}", abuf);
+        let decoded = if let Ok(abuf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
+            ServerMessage::decode_binary(&js_sys::Uint8Array::new(&abuf).to_vec())
         } else if let Ok(blob) = e.data().dyn_into::<web_sys::Blob>() {
             log!("message event, received blob: {:?}", blob);
+            return;
         } else if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
-            let event_result = GameMessageEvent::from_json(txt.as_string().unwrap().as_str());
-
-            if event_result.is_ok() {
-                let event = event_result.unwrap();
-                match event.event.as_str() {
-                    "players" => {
-                        update_player_list(
-                            &mut players_clone.borrow_mut(),
-                            event.content.as_str(),
-                            &current_game_clone.borrow()
-                        );
-                    }
-                    "games" => {
-                        update_game_list(event.content.as_str(), &ws_clone, &mut game_list);
-                    }
-                    "joined_game" => {
-                        joined_game(
-                            event.content.as_str(),
-                            &mut current_game_clone.borrow_mut(),
-                            &game_list,
-                            &players_clone.borrow()
-                        );
-                    }
-                    "new_move" => { new_move(event.content.as_str(), &mut game_clone.borrow_mut()) }
-                    "current_state" => {
-                        start_game(
-                            event.content.as_str(),
-                            &mut game_clone.borrow_mut(),
-                            &ws_clone,
-                            &players_clone
-                        );
-                    }
-                    "player_image" => {
-                        update_player_image(
-                            &mut players_clone.borrow_mut(),
-                            event.content.as_str()
-                        );
-                    }
-                    _ => {
-                        warn!("Unrecognized event: {:?} {:?}", event.event, event.content);
-                    }
-                }
-            }
+            ServerMessage::decode(txt.as_string().unwrap().as_str())
         } else {
             log!("message event, received Unknown: {:?}", e.data());
+            return;
+        };
+
+        let message = match decoded {
+            Ok(message) => message,
+            Err(err @ ProtocolError::UnknownEvent(_)) => {
+                warn!("{}", err);
+                return;
+            }
+            Err(err) => {
+                notify(Level::Error, &err.to_string());
+                return;
+            }
+        };
+
+        if let Some(event) = connection.dispatch(message) {
+            let transport: Rc<dyn Transport> = Rc::new(connection.clone());
+            handle_event(event, &session_clone, &game_clone, &transport);
         }
-    });
-    // set message event handler on WebSocket
-    ws.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-    // forget the callback to keep it alive
-    onmessage_callback.forget();
+    };
 
-    let onerror_callback = Closure::<dyn FnMut(_)>::new(move |e: ErrorEvent| {
-        log!("error event: {:?}", e);
-    });
-    ws.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
-    onerror_callback.forget();
+    let session_clone = session.clone();
+    let game_clone = game.clone();
+    let on_resume = move |connection: &Connection| {
+        let transport: Rc<dyn Transport> = Rc::new(connection.clone());
+        rejoin_game(&session_clone, &game_clone, &transport);
+    };
+
+    Connection::new(server_url(), on_message, on_resume)
+}
 
-    let cloned_ws = ws.clone();
-    let onopen_callback = Closure::<dyn FnMut()>::new(move || {
-        log!("socket opened");
-        match cloned_ws.send_with_str("{\"event\":\"players\",\"content\":\"\"}") {
-            Ok(_) => log!("message successfully sent"),
-            Err(err) => log!("error sending message: {:?}", err),
+/// Gets back into the current game on a new socket, the server may have
+/// dropped us from it along with the old one. Leaves the game if it can't.
+fn rejoin_game(
+    session: &Rc<RefCell<Session>>,
+    game: &Rc<RefCell<Option<Game>>>,
+    transport: &Rc<dyn Transport>
+) {
+    let event = match session.borrow().resume_event() {
+        Some(event) => event,
+        None => {
+            return;
         }
-    });
-    ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-    onopen_callback.forget();
-    ws
+    };
+    let session = session.clone();
+    let game = game.clone();
+    let transport_clone = transport.clone();
+    transport.request(
+        &event,
+        Box::new(move |outcome| {
+            let message = match failure_message("Rejoining the game", &outcome) {
+                Some(message) => message,
+                None => {
+                    return;
+                }
+            };
+            notify(Level::Error, &message);
+            session.borrow_mut().leave_game();
+            left_game(&mut game.borrow_mut(), &transport_clone);
+            display_rematch(&session.borrow());
+            display_spectators(&session.borrow());
+            display_chat(&session.borrow());
+        })
+    );
+    transport.send(&ClientEvent::CurrentState);
 }
 
-fn update_player_image(players: &mut Vec<Player>, content: &str) {
-    let result = PlayerImageResponse::from_json(content);
-    if result.is_err() {
-        error!("{:?}", result.err());
-        return;
+/// Applies an event to the session and updates the page to match, for
+/// received events and for those the client learns about otherwise.
+pub(crate) fn handle_event(
+    event: ServerEvent,
+    session: &Rc<RefCell<Session>>,
+    game: &Rc<RefCell<Option<Game>>>,
+    transport: &Rc<dyn Transport>
+) {
+    let result = session.borrow_mut().apply(event);
+    match result {
+        Ok(Update::Players) => {
+            let session = session.borrow();
+            display_players(&session);
+            display_turn(&session);
+            display_chat(&session);
+            display_own_name(&session);
+        }
+        Ok(Update::Games) => {
+            let session = session.borrow();
+            GAME_LIST.with(|list| {
+                update_game_list(&mut list.borrow_mut(), &session.games, transport);
+            });
+            display_turn(&session);
+            // Parameters and host may have changed
+            if let Some(current_game) = &session.current_game {
+                display_game_info(current_game);
+                display_players(&session);
+                display_host_controls(&session);
+            }
+        }
+        Ok(Update::JoinedGame) => {
+            // Coming from a rematch, the finished game is still displayed
+            close_board(&mut game.borrow_mut());
+            display_rematch(&session.borrow());
+            joined_game(&session.borrow());
+            display_turn(&session.borrow());
+            display_chat(&session.borrow());
+        }
+        Ok(Update::GameStarted) => {
+            start_game(game, session, transport);
+            show_local_win(&mut game.borrow_mut(), &session.borrow());
+            display_history(&game.borrow());
+            display_turn(&session.borrow());
+        }
+        Ok(Update::NewMove) => {
+            show_local_win(&mut game.borrow_mut(), &session.borrow());
+            display_history(&game.borrow());
+            display_turn(&session.borrow());
+        }
+        Ok(Update::GameOver) => {
+            game_over(&mut game.borrow_mut(), &session.borrow());
+            display_turn(&session.borrow());
+            display_rematch(&session.borrow());
+        }
+        Ok(Update::RematchProposed) => {
+            display_rematch(&session.borrow());
+        }
+        Ok(Update::RematchDeclined(player)) => {
+            let session = session.borrow();
+            let name = session.player_name(player).unwrap_or("A player");
+            notify(Level::Info, &format!("{} declined the rematch", name));
+            display_rematch(&session);
+        }
+        Ok(Update::LeftGame) => {
+            left_game(&mut game.borrow_mut(), transport);
+            display_rematch(&session.borrow());
+            display_spectators(&session.borrow());
+            display_chat(&session.borrow());
+        }
+        Ok(Update::Watching) => {
+            watching_game(&session.borrow());
+            display_chat(&session.borrow());
+        }
+        Ok(Update::Chat(game_id)) => {
+            let session = session.borrow();
+            if session.current_game.as_ref().is_some_and(|g| g.id == game_id) {
+                display_chat(&session);
+            }
+        }
+        Ok(Update::Viewers) => {
+            display_spectators(&session.borrow());
+        }
+        Ok(Update::PlayerImage) => {
+            display_turn(&session.borrow());
+        }
+        Ok(Update::Pong) => {}
+        Ok(Update::Error(err)) => {
+            notify(Level::Error, &server_error_message(&err));
+        }
+        Ok(Update::Encoding) => {
+            log!("Server encoding: {:?}", session.borrow().encoding);
+        }
+        Ok(Update::Hello(compatibility)) => {
+            display_compatibility(compatibility, &session.borrow());
+        }
+        Err(err) => {
+            notify(Level::Warning, &err);
+        }
     }
-
-    set_image(players, result.unwrap());
 }
 
-fn update_player_list(players: &mut Vec<Player>, content: &str, current_game: &Option<GameInfo>) {
-    let player_list: Vec<Player> = serde_wasm_bindgen
-        ::from_value(JSON::parse(content).unwrap())
-        .unwrap();
+fn display_compatibility(compatibility: Compatibility, session: &Session) {
+    let banner = get_element_by_id("version-banner");
+    let server_version = session.server.as_ref().map_or("unknown", |s| s.version.as_str());
+    let client_version = env!("CARGO_PKG_VERSION");
 
-    merge_players(players, &player_list);
-
-    display_players(&player_list, current_game);
+    let message = match compatibility {
+        Compatibility::Compatible => {
+            log!("Connected to server {}", server_version);
+            banner.set_class_name("hidden");
+            return;
+        }
+        Compatibility::ClientOutdated =>
+            format!(
+                "Client outdated: version {} is not supported by server {}, please reload.",
+                client_version,
+                server_version
+            ),
+        Compatibility::ServerOutdated =>
+            format!(
+                "Server outdated: server {} is too old for client {}.",
+                server_version,
+                client_version
+            ),
+    };
+    error!("{}", message);
+    banner.set_text_content(Some(&message));
+    banner.set_class_name("");
 }
 
-fn display_players(player_list: &Vec<Player>, current_game: &Option<GameInfo>) {
+fn display_players(session: &Session) {
+    let current_game = &session.current_game;
     let list: HtmlElement = players_div();
     list.set_inner_html("");
 
     let list_game = get_element_by_id("game-player-list");
     list_game.set_inner_html("");
 
-    for p in player_list {
+    for p in &session.player_list {
         debug!("Player: {} {} {:?} {}", p.id, p.name, p.joined_game_id, p.ready);
         let div = document().create_element("div").expect("Unable to create div");
         div.set_text_content(Some(format!("{}#{}", p.name, p.id).as_str()));
@@ -156,59 +273,247 @@ fn display_players(player_list: &Vec<Player>, current_game: &Option<GameInfo>) {
         {
             continue;
         }
+        if session.is_host() && Some(p.id) != session.player_id() {
+            add_player_action(&div, "Kick", "kick", p.id);
+            add_player_action(&div, "Make host", "host", p.id);
+        }
         list_game.append_child(&div).expect("Unable to add player to list");
     }
 }
-fn update_game_list(content: &str, ws: &WebSocket, game_list: &Rc<RefCell<Vec<GameInfo>>>) {
-    let games: Vec<GameInfo> = serde_wasm_bindgen
-        ::from_value(JSON::parse(content).unwrap())
-        .unwrap();
-    *game_list.borrow_mut() = games;
 
-    let list = games_div();
-    list.set_inner_html("");
+/// Adds a host action button, handled by the listener on `#game-player-list`.
+fn add_player_action(parent: &Element, label: &str, action: &str, player: u32) {
+    let button = document().create_element("button").expect("Unable to create button");
+    button.set_class_name("button player-action");
+    button.set_text_content(Some(label));
+    button.set_attribute("data-action", action).expect("Unable to set player action");
+    button.set_attribute("data-player", &player.to_string()).expect("Unable to set player");
+    parent.append_child(&button).expect("Unable to add player action");
+}
 
-    let game_list_clone = game_list.clone();
-
-    for i in 0..game_list.borrow().len() {
-        let game_list_borrow = game_list_clone.borrow();
-        let g = game_list_borrow[i].clone();
-        debug!(
-            "GameInfo: {} {} {} {} {} {} {} {}",
-            g.id,
-            g.player_list.len(),
-            g.creator,
-            g.current_turn,
-            g.hotjoin,
-            g.player_limit,
-            g.running,
-            g.win_length
+/// Shows the host controls, filled with the current parameters when they appear.
+fn display_host_controls(session: &Session) {
+    let controls = get_element_by_id("host-controls");
+    let current_game = match &session.current_game {
+        Some(current_game) if session.is_host() => current_game,
+        _ => {
+            controls.set_class_name("hidden");
+            return;
+        }
+    };
+    if controls.class_name() == "hidden" {
+        get_input_element_by_id("host-size-w").set_value(&current_game.width.to_string());
+        get_input_element_by_id("host-size-h").set_value(&current_game.height.to_string());
+        get_input_element_by_id("host-hotjoin").set_checked(current_game.hotjoin);
+        get_input_element_by_id("host-win-length").set_value(
+            &current_game.win_length.to_string()
         );
+        controls.set_class_name("");
+    }
+}
+/// Fills the settings name input with our name, unless it is being edited.
+fn display_own_name(session: &Session) {
+    let name = match session.player_id().and_then(|id| session.player_name(id)) {
+        Some(name) => name,
+        None => {
+            return;
+        }
+    };
+    let input = get_input_element_by_id("settings-name");
+    let editing = document()
+        .active_element()
+        .is_some_and(|active| active == *input.as_ref());
+    if !editing {
+        input.set_value(name);
+    }
+}
+
+/// Lists the current game's players, highlighting the one whose turn it is.
+fn display_turn(session: &Session) {
+    let list = get_element_by_id("turn-players");
+    list.set_inner_html("");
+    let status = get_element_by_id("turn-status");
+    status.set_text_content(session.turn_status().as_deref());
+
+    let indicator = get_element_by_id("turn-indicator");
+    indicator.set_class_name(if session.can_move() { "" } else { "waiting" });
+
+    let current_game = match &session.current_game {
+        Some(current_game) => current_game,
+        None => {
+            return;
+        }
+    };
+    for &id in &current_game.player_list {
         let div = document().create_element("div").expect("Unable to create div");
-        div.set_text_content(Some(format!("{} - {} players", g.id, g.player_list.len()).as_str()));
+        div.set_class_name(
+            if session.turn == Some(id) { "turn-player active" } else { "turn-player" }
+        );
+
+        let image = session.players
+            .iter()
+            .find(|p| p.id == id)
+            .and_then(|p| p.image_data());
+        if let Some(data) = image {
+            let img = document().create_element("img").expect("Unable to create img");
+            img.set_attribute("src", &format!("data:image/png;base64,{}", data))
+                .expect("Unable to set image source");
+            div.append_child(&img).expect("Unable to add avatar");
+        }
 
-        let ws_clone = ws.clone();
-        add_event_listener(&div, "click", move |_| {
-            send(&ws_clone, "join_game", GameJoinData::new(g.id).to_string().as_str());
-        });
+        let name = document().create_element("span").expect("Unable to create span");
+        name.set_text_content(session.player_name(id).or(Some("Unknown player")));
+        div.append_child(&name).expect("Unable to add name");
 
-        list.append_child(&div).expect("Unable to add game to list");
+        list.append_child(&div).expect("Unable to add player to list");
     }
 }
 
-fn joined_game(
-    content: &str,
-    current_game: &mut Option<GameInfo>,
-    game_list: &Rc<RefCell<Vec<GameInfo>>>,
-    player_list: &Vec<Player>
+/// Entry of `#game-list`, its listeners are released together with it.
+struct GameEntry {
+    div: Element,
+    listeners: Vec<EventListener>,
+}
+
+fn update_game_list(
+    view: &mut GameList<GameEntry>,
+    games: &[GameInfo],
+    transport: &Rc<dyn Transport>
 ) {
-    let data_result = GameJoinData::from_json(&content);
-    if data_result.is_err() {
-        error!("Unable to parse game join data info, either client or server may be out of date.");
-        return;
+    let list = games_div();
+    view.sync(
+        games,
+        |g, next| {
+            let div = document().create_element("div").expect("Unable to create div");
+            let mut entry = GameEntry { div, listeners: Vec::new() };
+            render_game_entry(&mut entry, g, transport);
+            list.insert_before(&entry.div, next.map(|next| next.div.as_ref())).expect(
+                "Unable to add game to list"
+            );
+            entry
+        },
+        |g, entry| render_game_entry(entry, g, transport),
+        |entry, next| {
+            list.insert_before(&entry.div, next.map(|next| next.div.as_ref())).expect(
+                "Unable to move game in list"
+            );
+        },
+        |entry| entry.div.remove()
+    );
+}
+
+fn render_game_entry(entry: &mut GameEntry, g: &GameInfo, transport: &Rc<dyn Transport>) {
+    debug!(
+        "GameInfo: {} {} {} {} {} {} {} {}",
+        g.id,
+        g.player_list.len(),
+        g.creator,
+        g.current_turn,
+        g.hotjoin,
+        g.player_limit,
+        g.running,
+        g.win_length
+    );
+    entry.listeners.clear();
+    let div = &entry.div;
+    div.set_text_content(Some(format!("{} - {} players", g.id, g.player_list.len()).as_str()));
+
+    let transport_clone = transport.clone();
+    let id = g.id;
+    // Running games without hotjoin can only be watched
+    let watch_only = g.running && !g.hotjoin;
+    entry.listeners.push(
+        EventListener::new(div, "click", move |_| {
+            if watch_only {
+                watch_game(transport_clone.as_ref(), id);
+            } else {
+                transport_clone.request(
+                    &ClientEvent::JoinGame(GameJoinData::new(id)),
+                    notify_failure("Joining game")
+                );
+            }
+        })
+    );
+
+    if g.running {
+        let button = document().create_element("button").expect("Unable to create button");
+        button.set_class_name("button");
+        button.set_text_content(Some("Watch"));
+        let transport_clone = transport.clone();
+        entry.listeners.push(
+            EventListener::new(&button, "click", move |e| {
+                e.stop_propagation();
+                watch_game(transport_clone.as_ref(), id);
+            })
+        );
+        div.append_child(&button).expect("Unable to add watch button");
     }
-    let data = data_result.unwrap();
-    log!("Joined game: {}", data.id);
+}
+
+fn watch_game(transport: &dyn Transport, id: u32) {
+    transport.request(
+        &ClientEvent::WatchGame(GameJoinData::new(id)),
+        notify_failure("Watching game")
+    );
+}
+
+fn watching_game(session: &Session) {
+    log!("Watching game: {:?}", session.current_game.as_ref().map(|g| g.id));
+    let menu = get_element_by_id("menu");
+    menu.set_class_name(menu.class_name().add(" hidden").as_str());
+    display_spectators(session);
+    // The game itself is shown once the server sends its current state
+}
+
+fn display_spectators(session: &Session) {
+    let badge = get_element_by_id("spectator-badge");
+    let text = match (session.spectating, session.viewers) {
+        (false, 0) => {
+            badge.set_class_name("hidden");
+            return;
+        }
+        (true, viewers) => format!("Spectating \u{b7} {} watching", viewers),
+        (false, viewers) => format!("{} watching", viewers),
+    };
+    badge.set_text_content(Some(&text));
+    badge.set_class_name("");
+}
+
+/// Shows the chat of the current game, hidden outside of games.
+fn display_chat(session: &Session) {
+    let panel = get_element_by_id("chat");
+    let current_game = match &session.current_game {
+        Some(current_game) => current_game,
+        None => {
+            panel.set_class_name("hidden");
+            return;
+        }
+    };
+    panel.set_class_name("");
+
+    let list = get_element_by_id("chat-messages");
+    list.set_inner_html("");
+    for message in session.chat.messages(current_game.id) {
+        let div = document().create_element("div").expect("Unable to create div");
+        div.set_class_name("chat-message");
+
+        let name = document().create_element("span").expect("Unable to create span");
+        name.set_class_name("chat-name");
+        name.set_text_content(session.player_name(message.player).or(Some("Unknown player")));
+        div.append_child(&name).expect("Unable to add name");
+
+        let text = document().create_element("span").expect("Unable to create span");
+        text.set_text_content(Some(&message.text));
+        div.append_child(&text).expect("Unable to add message");
+
+        list.append_child(&div).expect("Unable to add message to chat");
+    }
+    list.set_scroll_top(list.scroll_height());
+}
+
+fn joined_game(session: &Session) {
+    let current_game = session.current_game.as_ref().unwrap();
+    log!("Joined game: {}", current_game.id);
 
     let menu = get_element_by_id("menu");
     let lobby = get_element_by_id("lobby");
@@ -216,81 +521,183 @@ fn joined_game(
     menu.set_class_name(menu.class_name().add(" hidden").as_str());
     lobby.set_class_name("fullscreen");
 
-    let index = game_list
-        .borrow()
-        .iter()
-        .position(|p| { p.id == data.id })
-        .unwrap();
-
-    *current_game = Some(game_list.borrow()[index].clone());
+    display_game_info(current_game);
+    display_players(session);
+    display_host_controls(session);
+}
 
-    // Display game info
-    get_element_by_id("game-id").set_text_content(
-        Some(current_game.as_ref().unwrap().id.to_string().as_str())
-    );
+fn display_game_info(current_game: &GameInfo) {
+    get_element_by_id("game-id").set_text_content(Some(current_game.id.to_string().as_str()));
     get_element_by_id("game-size-w").set_text_content(
-        Some(current_game.as_ref().unwrap().width.to_string().as_str())
+        Some(current_game.width.to_string().as_str())
     );
     get_element_by_id("game-size-h").set_text_content(
-        Some(current_game.as_ref().unwrap().height.to_string().as_str())
+        Some(current_game.height.to_string().as_str())
     );
     get_element_by_id("game-hotjoin").set_text_content(
-        Some(current_game.as_ref().unwrap().hotjoin.to_string().as_str())
+        Some(current_game.hotjoin.to_string().as_str())
     );
     get_element_by_id("game-win-length").set_text_content(
-        Some(current_game.as_ref().unwrap().win_length.to_string().as_str())
+        Some(current_game.win_length.to_string().as_str())
     );
-
-    display_players(player_list, current_game);
 }
 
-fn start_game(
-    content: &str,
-    game: &mut Option<Game>,
-    ws: &WebSocket,
-    players: &Rc<RefCell<Vec<Player>>>
+pub(crate) fn start_game(
+    game: &Rc<RefCell<Option<Game>>>,
+    session: &Rc<RefCell<Session>>,
+    transport: &Rc<dyn Transport>
 ) {
-    let grid_result = Grid::from_json(content);
-    if grid_result.is_err() {
-        error!("{}", grid_result.err().unwrap());
-        return;
-    }
-    let grid = grid_result.unwrap();
-    log!("{:?}", grid);
+    log!("{:?}", session.borrow().board);
 
-    *game = Some(Game::new("game", grid, ws, players));
+    *game.borrow_mut() = Some(Game::new("game", session, transport));
     let lobby = get_element_by_id("lobby");
     lobby.set_class_name(lobby.class_name().add(" hidden").as_str());
 
     let game_container = get_element_by_id("game-container");
     game_container.set_class_name("");
+    start_render_loop(game);
 }
 
-fn new_move(content: &str, game: &mut Option<Game>) {
-    if game.is_none() {
+/// Renders the game every frame, until a frame finds it torn down.
+fn start_render_loop(game: &Rc<RefCell<Option<Game>>>) {
+    if RENDERING.with(|rendering| rendering.replace(true)) {
         return;
     }
+    let canvas: HtmlCanvasElement = get_element_by_id("game")
+        .dyn_into()
+        .expect("Not HtmlCanvasElement type");
+    let f = Rc::new(RefCell::new(None::<Closure<dyn FnMut()>>));
+    let g = f.clone();
+    let game = game.clone();
+
+    *g.borrow_mut() = Some(
+        Closure::new(move || {
+            match game.borrow_mut().as_mut() {
+                Some(game) => {
+                    let width = canvas.client_width().max(0) as u32;
+                    let height = canvas.client_height().max(0) as u32;
+                    if canvas.width() != width || canvas.height() != height {
+                        canvas.set_width(width);
+                        canvas.set_height(height);
+                    }
+                    game.render();
+                }
+                None => {
+                    RENDERING.with(|rendering| rendering.set(false));
+                    // Dropping the closure ends the loop
+                    let _ = f.borrow_mut().take();
+                    return;
+                }
+            }
+            request_animation_frame(f.borrow().as_ref().unwrap());
+        })
+    );
+    request_animation_frame(g.borrow().as_ref().unwrap());
+}
 
-    let m = PlayerMove::from_json(content).expect("Unable to parse JSON");
-    game.as_mut().unwrap().add_move(m);
+fn request_animation_frame(f: &Closure<dyn FnMut()>) {
+    window()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .expect("should register `requestAnimationFrame` OK");
 }
 
-pub fn send(ws: &WebSocket, event: &str, content: &str) {
-    let msg = GameMessageEvent::new(event, content);
-
-    // Check if websocket is open
-    if ws.ready_state() != 1 {
-        // Wait for websocket to be open
-        let ws_clone = ws.clone();
-        let cb = Closure::wrap(
-            Box::new(move || {
-                send(&ws_clone, msg.event.as_str(), msg.content.as_str());
-            }) as Box<dyn FnMut()>
-        );
-        set_timeout(&cb, 1000);
-        cb.forget();
-        return;
+/// Updates the history controls with the ply shown.
+pub(crate) fn display_history(game: &Option<Game>) {
+    let (ply, total) = match game {
+        Some(game) => game.history_position(),
+        None => {
+            return;
+        }
+    };
+    let slider = get_input_element_by_id("history-slider");
+    slider.set_max(&total.to_string());
+    slider.set_value(&ply.to_string());
+    let text = if ply == total {
+        format!("Live, move {}", total)
+    } else {
+        format!("Move {} of {}", ply, total)
+    };
+    get_element_by_id("history-ply").set_text_content(Some(&text));
+    get_element_by_id("history").set_class_name(if ply == total { "" } else { "reviewing" });
+}
+
+/// Highlights a line completed on the board without waiting for the server's `game_over`.
+fn show_local_win(game: &mut Option<Game>, session: &Session) {
+    if let (Some(game), Some(win)) = (game.as_mut(), &session.win) {
+        if !game.is_finished() {
+            game.finish(&win.cells);
+        }
     }
-    let text = msg.to_string();
-    ws.send_with_str(text.as_str()).expect("Unable to send message");
+}
+
+/// Tears down the game and lobby and goes back to the menu.
+fn close_board(game: &mut Option<Game>) {
+    *game = None;
+    get_element_by_id("results").set_class_name("fullscreen hidden");
+    get_element_by_id("game-container").set_class_name("hidden");
+}
+
+pub(crate) fn left_game(game: &mut Option<Game>, transport: &Rc<dyn Transport>) {
+    log!("Left game");
+    close_board(game);
+    get_element_by_id("lobby").set_class_name("fullscreen hidden");
+    get_element_by_id("menu").set_class_name("fullscreen");
+
+    transport.send(&ClientEvent::Games);
+    transport.send(&ClientEvent::Players);
+}
+
+/// Shows the pending rematch offer, only players who can still answer get the buttons.
+fn display_rematch(session: &Session) {
+    let offer_div = get_element_by_id("rematch-offer");
+    let rematch_btn = get_element_by_id("results-rematch-btn");
+    rematch_btn.set_class_name(
+        if session.rematch.is_none() && session.rematch_proposal().is_some() {
+            "button"
+        } else {
+            "button hidden"
+        }
+    );
+
+    let offer = match &session.rematch {
+        Some(offer) => offer,
+        None => {
+            offer_div.set_class_name("hidden");
+            return;
+        }
+    };
+    let own = session.player_id() == Some(offer.proposer);
+    let text = if own {
+        "Rematch proposed, waiting for the other players".to_string()
+    } else {
+        let name = session.player_name(offer.proposer).unwrap_or("Unknown player");
+        format!("{} proposes a rematch", name)
+    };
+    get_element_by_id("rematch-text").set_text_content(Some(&text));
+    let buttons_class = if own { "button hidden" } else { "button" };
+    get_element_by_id("rematch-accept-btn").set_class_name(buttons_class);
+    get_element_by_id("rematch-decline-btn").set_class_name(buttons_class);
+    offer_div.set_class_name("");
+}
+
+fn game_over(game: &mut Option<Game>, session: &Session) {
+    let result = session.result.as_ref().expect("Game over without a result");
+    let elapsed = game.as_mut().and_then(|game| {
+        game.finish(&result.winning_cells);
+        game.elapsed_ms()
+    });
+    let duration = elapsed.map_or_else(|| "-".to_string(), format_duration);
+
+    let title = match result.winner {
+        Some(id) if !result.is_draw() =>
+            format!("{} won", session.player_name(id).unwrap_or("Unknown player")),
+        _ => "Draw".to_string(),
+    };
+    log!("Game over: {}", title);
+
+    let moves = session.board.as_ref().map_or(0, |board| board.moves().len());
+    get_element_by_id("results-title").set_text_content(Some(&title));
+    get_element_by_id("results-moves").set_text_content(Some(&moves.to_string()));
+    get_element_by_id("results-duration").set_text_content(Some(&duration));
+    get_element_by_id("results").set_class_name("fullscreen");
 }
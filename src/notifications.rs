@@ -0,0 +1,84 @@
+use wasm_bindgen::{ closure::Closure, JsCast };
+
+use crate::{
+    error,
+    log,
+    pendingrequests::{ OnReply, Outcome },
+    servererror::ServerError,
+    utils::{ document, get_element_by_id, window, EventListener },
+    warn,
+};
+
+/// How long a notification stays on screen.
+const DISPLAY_MS: i32 = 6000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+impl Level {
+    pub fn class_name(&self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Text shown when a request fails, `None` if it succeeded.
+pub fn failure_message(action: &str, outcome: &Outcome) -> Option<String> {
+    match outcome {
+        Outcome::Accepted | Outcome::Sent => None,
+        Outcome::Rejected(err) => Some(format!("{} failed: {}", action, err.message)),
+        Outcome::TimedOut => Some(format!("{} failed: the server did not respond", action)),
+        Outcome::Disconnected => Some(format!("{} failed: the connection was lost", action)),
+    }
+}
+
+/// Text shown for an error the server sent without it answering one of our requests.
+pub fn server_error_message(err: &ServerError) -> String {
+    match &err.request {
+        Some(request) => format!("Server refused {}: {}", request, err.message),
+        None => format!("Server error: {}", err.message),
+    }
+}
+
+/// Reply callback that notifies the player when a request fails.
+pub fn notify_failure(action: &'static str) -> OnReply {
+    Box::new(move |outcome| {
+        if let Some(message) = failure_message(action, &outcome) {
+            notify(Level::Error, &message);
+        }
+    })
+}
+
+/// Logs a message and shows it in the notification area for a few seconds.
+pub fn notify(level: Level, message: &str) {
+    match level {
+        Level::Info => log!("{}", message),
+        Level::Warning => warn!("{}", message),
+        Level::Error => error!("{}", message),
+    }
+
+    let div = document().create_element("div").expect("Unable to create div");
+    div.set_class_name(&format!("notification {}", level.class_name()));
+    div.set_text_content(Some(message));
+    get_element_by_id("notifications")
+        .append_child(&div)
+        .expect("Unable to add notification");
+
+    let clicked = div.clone();
+    let listener = EventListener::new(&div, "click", move |_| clicked.remove());
+
+    // Dropping the listener frees its closure along with the notification
+    let remove = Closure::once_into_js(move || {
+        drop(listener);
+        div.remove();
+    });
+    window()
+        .set_timeout_with_callback_and_timeout_and_arguments_0(remove.unchecked_ref(), DISPLAY_MS)
+        .expect("should register `setTimeout` OK");
+}
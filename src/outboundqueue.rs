@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+
+/// What to discard when a message is pushed into a full queue.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DropPolicy {
+    /// Discard the message that has been waiting the longest.
+    DropOldest,
+    /// Discard the message being pushed.
+    DropNewest,
+}
+
+/// Bounded FIFO of encoded messages waiting for the socket to open.
+#[derive(Debug)]
+pub struct OutboundQueue<T = String> {
+    messages: VecDeque<T>,
+    capacity: usize,
+    policy: DropPolicy,
+    dropped: usize,
+}
+impl<T> OutboundQueue<T> {
+    pub fn new(capacity: usize, policy: DropPolicy) -> Self {
+        Self {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+            policy,
+            dropped: 0,
+        }
+    }
+    /// Queues a message. Returns the message that was dropped to make room, if any.
+    pub fn push(&mut self, message: T) -> Option<T> {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return Some(message);
+        }
+        let dropped = if self.messages.len() >= self.capacity {
+            self.dropped += 1;
+            match self.policy {
+                DropPolicy::DropOldest => self.messages.pop_front(),
+                DropPolicy::DropNewest => {
+                    return Some(message);
+                }
+            }
+        } else {
+            None
+        };
+        self.messages.push_back(message);
+        dropped
+    }
+    /// Puts a message that failed to send back at the front of the queue.
+    /// Returns it if the queue is full.
+    pub fn requeue(&mut self, message: T) -> Option<T> {
+        if self.messages.len() >= self.capacity {
+            self.dropped += 1;
+            return Some(message);
+        }
+        self.messages.push_front(message);
+        None
+    }
+    pub fn pop(&mut self) -> Option<T> {
+        self.messages.pop_front()
+    }
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Number of messages discarded since the queue was created.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
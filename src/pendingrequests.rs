@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::{ protocol::{ ServerEvent, ServerMessage }, servererror::ServerError };
+
+pub type RequestId = u32;
+
+/// How the server answered a request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// The server replied with a regular event.
+    Accepted,
+    /// The server replied with an `error` event.
+    Rejected(ServerError),
+    /// No reply arrived in time.
+    TimedOut,
+    /// The connection was lost before the server replied.
+    Disconnected,
+    /// Sent to a server that doesn't echo request ids, so no reply will be matched.
+    Sent,
+}
+impl Outcome {
+    pub fn for_event(event: &ServerEvent) -> Self {
+        match event {
+            ServerEvent::Error(err) => Outcome::Rejected(err.clone()),
+            _ => Outcome::Accepted,
+        }
+    }
+}
+
+pub type OnReply = Box<dyn FnOnce(Outcome)>;
+
+/// A finished request, whose callback still has to be run.
+pub struct Reply {
+    pub name: String,
+    pub outcome: Outcome,
+    on_reply: OnReply,
+}
+impl Reply {
+    pub fn run(self) {
+        (self.on_reply)(self.outcome);
+    }
+}
+
+struct Pending {
+    name: String,
+    /// `None` while the request waits in the outbound queue.
+    sent_at: Option<f64>,
+    on_reply: OnReply,
+}
+
+/// Requests that are waiting for a reply from the server.
+///
+/// Callbacks are handed back as `Reply`s instead of being run here, so
+/// they are free to send new requests. Times are in milliseconds, and the
+/// timeout only runs once a request has actually been written to the socket.
+pub struct PendingRequests {
+    next_id: RequestId,
+    timeout_ms: f64,
+    pending: HashMap<RequestId, Pending>,
+}
+impl PendingRequests {
+    pub fn new(timeout_ms: f64) -> Self {
+        Self {
+            next_id: 1,
+            timeout_ms,
+            pending: HashMap::new(),
+        }
+    }
+    pub fn timeout_ms(&self) -> f64 {
+        self.timeout_ms
+    }
+    /// Registers a request and returns the id to send it with.
+    pub fn insert(&mut self, name: String, on_reply: OnReply) -> RequestId {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.pending.insert(id, Pending { name, sent_at: None, on_reply });
+        id
+    }
+    /// Starts the timeout of a request once it has been written.
+    pub fn sent(&mut self, id: RequestId, now: f64) {
+        if let Some(pending) = self.pending.get_mut(&id) {
+            pending.sent_at = Some(now);
+        }
+    }
+    /// Ends a request early, e.g. one that will never be written.
+    pub fn fail(&mut self, id: RequestId, outcome: Outcome) -> Option<Reply> {
+        let pending = self.pending.remove(&id)?;
+        Some(Reply { name: pending.name, outcome, on_reply: pending.on_reply })
+    }
+    /// Removes the request answered by `message`, if any.
+    pub fn resolve(&mut self, message: &ServerMessage) -> Option<Reply> {
+        self.fail(message.request_id?, Outcome::for_event(&message.event))
+    }
+    /// Removes every request sent more than the timeout ago.
+    pub fn expire(&mut self, now: f64) -> Vec<Reply> {
+        let timeout = self.timeout_ms;
+        let expired: Vec<RequestId> = self.pending
+            .iter()
+            .filter(|(_, p)| p.sent_at.is_some_and(|sent_at| now - sent_at >= timeout))
+            .map(|(id, _)| *id)
+            .collect();
+        self.fail_all(expired, Outcome::TimedOut)
+    }
+    /// Removes every request written to a socket that is now gone. Queued
+    /// ones stay for the next socket.
+    pub fn disconnected(&mut self) -> Vec<Reply> {
+        let lost: Vec<RequestId> = self.pending
+            .iter()
+            .filter(|(_, p)| p.sent_at.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+        self.fail_all(lost, Outcome::Disconnected)
+    }
+    fn fail_all(&mut self, ids: Vec<RequestId>, outcome: Outcome) -> Vec<Reply> {
+        ids.into_iter()
+            .filter_map(|id| self.fail(id, outcome.clone()))
+            .collect()
+    }
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
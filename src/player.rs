@@ -1,28 +1,54 @@
 use serde::Deserialize;
-use web_sys::{ HtmlImageElement, WebSocket };
+use web_sys::HtmlImageElement;
 
-use crate::{ net::send, playerimageresponse::PlayerImageResponse };
+use crate::{
+    playerimageresponse::PlayerImageResponse,
+    protocol::ClientEvent,
+    transport::Transport,
+};
 
-#[derive(Deserialize, Clone)]
-pub(crate) struct Player {
+#[derive(Deserialize, Clone, Debug)]
+pub struct Player {
     pub id: u32,
     pub joined_game_id: Option<u32>,
     pub ready: bool,
     pub name: String,
+    /// Base64 encoded PNG received from the server.
+    #[serde(skip_deserializing)]
+    image_data: Option<String>,
     #[serde(skip_deserializing)]
     image: Option<HtmlImageElement>,
 }
 impl Player {
-    pub fn get_image(&mut self, ws: &WebSocket) -> HtmlImageElement {
+    pub fn new(id: u32, name: String, joined_game_id: Option<u32>, ready: bool) -> Self {
+        Self {
+            id,
+            joined_game_id,
+            ready,
+            name,
+            image_data: None,
+            image: None,
+        }
+    }
+    pub fn get_image(&mut self, transport: &dyn Transport) -> HtmlImageElement {
         if self.image.is_none() {
-            send(ws, "get_image", self.id.to_string().as_str());
-            self.image = Some(HtmlImageElement::new().unwrap());
+            let image = HtmlImageElement::new().unwrap();
+            if let Some(data) = &self.image_data {
+                image.set_src(format!("data:image/png;base64,{}", data).as_str());
+            } else {
+                transport.send(&ClientEvent::GetImage(self.id));
+            }
+            self.image = Some(image);
         }
         self.image.clone().unwrap()
     }
     pub fn set_image(&mut self, image: String) {
-        self.image = Some(HtmlImageElement::new().unwrap());
-        self.image.clone().unwrap().set_src(format!("data:image/png;base64,{}", image).as_str());
+        self.image_data = Some(image);
+        // Recreated from the new data on the next get_image
+        self.image = None;
+    }
+    pub fn image_data(&self) -> Option<&str> {
+        self.image_data.as_deref()
     }
 }
 impl PartialEq for Player {
@@ -33,9 +59,14 @@ impl PartialEq for Player {
 
 pub(crate) fn merge_players(players: &mut Vec<Player>, new_players: &Vec<Player>) {
     for p in new_players {
-        if players.contains(p) {
-        } else {
-            players.push(p.clone());
+        match players.iter_mut().find(|known| *known == p) {
+            // Keep the image, the rest may have changed on the server
+            Some(known) => {
+                known.name = p.name.clone();
+                known.joined_game_id = p.joined_game_id;
+                known.ready = p.ready;
+            }
+            None => players.push(p.clone()),
         }
     }
 }
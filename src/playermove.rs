@@ -1,14 +1,9 @@
 use serde::Deserialize;
 
-use crate::utils::{ Size, from_json };
+use crate::utils::Size;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct PlayerMove {
     pub player: i32,
     pub position: Size,
 }
-impl PlayerMove {
-    pub fn from_json(text: &str) -> Result<Self, String> {
-        from_json(text)
-    }
-}
@@ -0,0 +1,230 @@
+use std::fmt;
+
+use serde::{ Deserialize, Serialize, Serializer };
+
+use crate::{ binary, pendingrequests::RequestId };
+
+pub use crate::{
+    avatar::AvatarUpload,
+    chat::{ ChatMessage, ChatRequest },
+    encodingoptions::EncodingOptions,
+    gameinfo::GameInfo,
+    gamejoindata::GameJoinData,
+    gameresult::GameResult,
+    gameviewers::GameViewers,
+    heartbeat::Ping,
+    hello::{ Compatibility, Hello },
+    host::{ GameUpdate, PlayerAction },
+    gameparameters::GameParameters,
+    grid::Grid,
+    player::Player,
+    playerimageresponse::PlayerImageResponse,
+    playermove::PlayerMove,
+    rematch::{ RematchDeclined, RematchOffer, RematchProposal, RematchResponse },
+    servererror::ServerError,
+    settings::PlayerName,
+    utils::Size,
+};
+
+/// Events received from the server.
+///
+/// On the wire every event is a `{"event": ..., "content": ...}` object where
+/// `content` is itself a JSON document encoded as a string.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "event", content = "content", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Players(#[serde(with = "json_string")] Vec<Player>),
+    Games(#[serde(with = "json_string")] Vec<GameInfo>),
+    JoinedGame(#[serde(with = "json_string")] GameJoinData),
+    NewMove(#[serde(with = "json_string")] PlayerMove),
+    CurrentState(#[serde(with = "json_string")] Grid),
+    PlayerImage(#[serde(with = "json_string")] PlayerImageResponse),
+    Encoding(#[serde(with = "json_string")] EncodingOptions),
+    Hello(#[serde(with = "json_string")] Hello),
+    Pong(#[serde(with = "json_string")] Ping),
+    Error(#[serde(with = "json_string")] ServerError),
+    GameOver(#[serde(with = "json_string")] GameResult),
+    LeftGame(#[serde(with = "json_string")] GameJoinData),
+    /// We are now spectating the game.
+    Watching(#[serde(with = "json_string")] GameViewers),
+    /// The number of spectators of the current game changed.
+    Viewers(#[serde(with = "json_string")] GameViewers),
+    Chat(#[serde(with = "json_string")] ChatMessage),
+    RematchProposed(#[serde(with = "json_string")] RematchOffer),
+    RematchDeclined(#[serde(with = "json_string")] RematchDeclined),
+    GameAdded(#[serde(with = "json_string")] GameInfo),
+    GameUpdated(#[serde(with = "json_string")] GameInfo),
+    GameRemoved(#[serde(with = "json_string")] GameJoinData),
+}
+impl ServerEvent {
+    /// Wire names of every event above, used to tell unknown events from
+    /// known ones with bad content.
+    pub const NAMES: &'static [&'static str] = &[
+        "players",
+        "games",
+        "joined_game",
+        "new_move",
+        "current_state",
+        "player_image",
+        "encoding",
+        "hello",
+        "pong",
+        "error",
+        "game_over",
+        "left_game",
+        "watching",
+        "viewers",
+        "chat",
+        "rematch_proposed",
+        "rematch_declined",
+        "game_added",
+        "game_updated",
+        "game_removed",
+    ];
+
+    pub fn decode(text: &str) -> Result<Self, ProtocolError> {
+        ServerMessage::decode(text).map(|message| message.event)
+    }
+}
+
+/// A server event and the id of the request it answers, if any.
+///
+/// The id is sent next to the event as `"request_id"`.
+#[derive(Debug)]
+pub struct ServerMessage {
+    pub request_id: Option<RequestId>,
+    pub event: ServerEvent,
+}
+impl ServerMessage {
+    pub fn decode(text: &str) -> Result<Self, ProtocolError> {
+        let Frame { event, request_id } = serde_json
+            ::from_str(text)
+            .map_err(|e| ProtocolError::InvalidFrame(e.to_string()))?;
+
+        if !ServerEvent::NAMES.contains(&event.as_str()) {
+            return Err(ProtocolError::UnknownEvent(event));
+        }
+        let event = serde_json::from_str(text).map_err(|e| {
+            ProtocolError::MalformedContent { event, reason: e.to_string() }
+        })?;
+        Ok(Self { request_id, event })
+    }
+    /// Decodes a binary frame, see `binary`.
+    pub fn decode_binary(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        binary::decode(bytes)
+    }
+}
+
+/// Events sent to the server.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", content = "content", rename_all = "snake_case")]
+pub enum ClientEvent {
+    #[serde(serialize_with = "empty")]
+    Players,
+    #[serde(serialize_with = "empty")]
+    Games,
+    CreateGame(#[serde(with = "json_string")] GameParameters),
+    JoinGame(#[serde(with = "json_string")] GameJoinData),
+    #[serde(serialize_with = "empty")]
+    Ready,
+    AddBot(String),
+    Move(#[serde(with = "json_string")] Size),
+    GetImage(#[serde(with = "json_string")] u32),
+    #[serde(serialize_with = "empty")]
+    CurrentState,
+    Encoding(#[serde(with = "json_string")] EncodingOptions),
+    Hello(#[serde(with = "json_string")] Hello),
+    Ping(#[serde(with = "json_string")] Ping),
+    #[serde(serialize_with = "empty")]
+    LeaveGame,
+    /// Subscribes to a game's updates without taking a seat.
+    WatchGame(#[serde(with = "json_string")] GameJoinData),
+    Chat(#[serde(with = "json_string")] ChatRequest),
+    ProposeRematch(#[serde(with = "json_string")] RematchProposal),
+    RematchResponse(#[serde(with = "json_string")] RematchResponse),
+    SetName(#[serde(with = "json_string")] PlayerName),
+    SetImage(#[serde(with = "json_string")] AvatarUpload),
+    KickPlayer(#[serde(with = "json_string")] PlayerAction),
+    TransferHost(#[serde(with = "json_string")] PlayerAction),
+    UpdateGame(#[serde(with = "json_string")] GameUpdate),
+    StartGame(#[serde(with = "json_string")] GameJoinData),
+}
+impl ClientEvent {
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("Unable to serialize")
+    }
+    /// Encodes the event as a request the server will answer with `request_id`.
+    pub fn encode_request(&self, request_id: RequestId) -> String {
+        let mut value = serde_json::to_value(self).expect("Unable to serialize");
+        value["request_id"] = request_id.into();
+        value.to_string()
+    }
+    /// Event name used on the wire.
+    pub fn name(&self) -> String {
+        let value = serde_json::to_value(self).expect("Unable to serialize");
+        value["event"].as_str().unwrap_or_default().to_string()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    /// The message is not a valid `{"event", "content"}` object.
+    InvalidFrame(String),
+    /// The event name is not known to this client.
+    UnknownEvent(String),
+    /// The event is known, but its content could not be decoded.
+    MalformedContent {
+        event: String,
+        reason: String,
+    },
+    /// A binary frame could not be decoded.
+    InvalidBinary(String),
+}
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::InvalidFrame(reason) => write!(f, "Invalid message: {}", reason),
+            ProtocolError::UnknownEvent(event) => write!(f, "Unrecognized event: {}", event),
+            ProtocolError::MalformedContent { event, reason } =>
+                write!(
+                    f,
+                    "Unable to parse {} event, either client or server may be out of date: {}",
+                    event,
+                    reason
+                ),
+            ProtocolError::InvalidBinary(reason) => write!(f, "Invalid binary message: {}", reason),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Frame {
+    event: String,
+    #[serde(default)]
+    request_id: Option<RequestId>,
+}
+
+fn empty<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("")
+}
+
+/// (De)serializes a value as a JSON document nested inside a string.
+mod json_string {
+    use serde::{ de::{ DeserializeOwned, Error as _ }, ser::Error as _ };
+    use serde::{ Deserialize, Deserializer, Serialize, Serializer };
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        value: &T,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        let text = serde_json::to_string(value).map_err(S::Error::custom)?;
+        serializer.serialize_str(&text)
+    }
+
+    pub fn deserialize<'de, T: DeserializeOwned, D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<T, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        serde_json::from_str(&text).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,234 @@
+use std::{ convert::TryFrom, fmt };
+
+use crate::{
+    gameinfo::GameInfo,
+    gameresult::GameResult,
+    grid::{ Grid, MAX_SIZE },
+    playermove::PlayerMove,
+    utils::Size,
+};
+
+/// How a recorded game ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordResult {
+    Unfinished,
+    Draw,
+    Winner(u32),
+}
+
+/// A game in a PGN like text notation: `[Name "value"]` header tags, then one
+/// `<number>. <player> <x>,<y>` line per move. Lines starting with `;` are comments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub game_id: Option<u32>,
+    pub size: Size,
+    pub win_length: u32,
+    pub hotjoin: bool,
+    pub players: Vec<u32>,
+    pub result: RecordResult,
+    pub moves: Vec<PlayerMove>,
+}
+
+/// Why a record could not be imported.
+#[derive(Debug, PartialEq)]
+pub struct RecordError {
+    /// Offending line, starting at 1. `None` for a missing header tag.
+    pub line: Option<usize>,
+    pub message: String,
+}
+impl RecordError {
+    fn at(line: usize, message: impl Into<String>) -> Self {
+        Self { line: Some(line), message: message.into() }
+    }
+}
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "Line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl GameRecord {
+    pub fn new(info: &GameInfo, board: &Grid, result: Option<&GameResult>) -> Self {
+        let result = match result {
+            Some(result) if result.is_draw() => RecordResult::Draw,
+            Some(GameResult { winner: Some(winner), .. }) => RecordResult::Winner(*winner),
+            _ => RecordResult::Unfinished,
+        };
+        Self {
+            game_id: Some(info.id),
+            size: board.size,
+            win_length: info.win_length,
+            hotjoin: info.hotjoin,
+            players: info.player_list.clone(),
+            result,
+            moves: board.moves().to_vec(),
+        }
+    }
+
+    /// The board after every recorded move.
+    pub fn to_grid(&self) -> Grid {
+        Grid::new(self.size, self.moves.clone())
+    }
+
+    /// Parses a record, checking every move could have been played.
+    pub fn parse(text: &str) -> Result<Self, RecordError> {
+        let mut game_id = None;
+        let mut size = None;
+        let mut win_length = None;
+        let mut hotjoin = false;
+        let mut players = None;
+        let mut result = RecordResult::Unfinished;
+        let mut moves = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if !line.starts_with('[') {
+                moves.push((number, parse_move(line, moves.len() + 1, number)?));
+                continue;
+            }
+            if !moves.is_empty() {
+                return Err(RecordError::at(number, "Header tag after the moves"));
+            }
+            let (name, value) = parse_tag(line).ok_or_else(|| {
+                RecordError::at(number, "Malformed tag, expected [Name \"value\"]")
+            })?;
+            let invalid = || RecordError::at(number, format!("Invalid {} \"{}\"", name, value));
+            match name {
+                "Game" => {
+                    game_id = Some(value.parse().map_err(|_| invalid())?);
+                }
+                "Size" => {
+                    size = Some(parse_size(value).ok_or_else(invalid)?);
+                }
+                "WinLength" => {
+                    win_length = Some(value.parse().map_err(|_| invalid())?);
+                }
+                "Hotjoin" => {
+                    hotjoin = value.parse().map_err(|_| invalid())?;
+                }
+                "Players" => {
+                    let list: Result<Vec<u32>, _> = value
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect();
+                    players = Some(list.map_err(|_| invalid())?);
+                }
+                "Result" => {
+                    result = match value {
+                        "*" => RecordResult::Unfinished,
+                        "draw" => RecordResult::Draw,
+                        winner => RecordResult::Winner(winner.parse().map_err(|_| invalid())?),
+                    };
+                }
+                // Kept out of the record, like unknown PGN tags
+                _ => {}
+            }
+        }
+
+        let missing = |tag: &str| RecordError {
+            line: None,
+            message: format!("Missing {} tag", tag),
+        };
+        let record = Self {
+            game_id,
+            size: size.ok_or_else(|| missing("Size"))?,
+            win_length: win_length.ok_or_else(|| missing("WinLength"))?,
+            hotjoin,
+            players: players.ok_or_else(|| missing("Players"))?,
+            result,
+            moves: Vec::new(),
+        };
+        record.replay(moves)
+    }
+
+    /// Plays the parsed moves on an empty board, rejecting the first illegal one.
+    fn replay(mut self, moves: Vec<(usize, PlayerMove)>) -> Result<Self, RecordError> {
+        let mut grid = Grid::new(self.size, Vec::new());
+        for (line, m) in moves {
+            let known = u32::try_from(m.player).is_ok_and(|id| self.players.contains(&id));
+            if !known {
+                return Err(RecordError::at(line, format!("Unknown player {}", m.player)));
+            }
+            grid
+                .check_position(&m.position)
+                .map_err(|err| RecordError::at(line, err.to_string()))?;
+            grid.add(m.clone());
+            self.moves.push(m);
+        }
+        Ok(self)
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(id) = self.game_id {
+            writeln!(f, "[Game \"{}\"]", id)?;
+        }
+        writeln!(f, "[Size \"{}x{}\"]", self.size.x, self.size.y)?;
+        writeln!(f, "[WinLength \"{}\"]", self.win_length)?;
+        writeln!(f, "[Hotjoin \"{}\"]", self.hotjoin)?;
+        let players: Vec<String> = self.players
+            .iter()
+            .map(u32::to_string)
+            .collect();
+        writeln!(f, "[Players \"{}\"]", players.join(" "))?;
+        match self.result {
+            RecordResult::Unfinished => writeln!(f, "[Result \"*\"]")?,
+            RecordResult::Draw => writeln!(f, "[Result \"draw\"]")?,
+            RecordResult::Winner(id) => writeln!(f, "[Result \"{}\"]", id)?,
+        }
+        writeln!(f)?;
+        for (i, m) in self.moves.iter().enumerate() {
+            writeln!(f, "{}. {} {},{}", i + 1, m.player, m.position.x, m.position.y)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits `[Name "value"]`.
+fn parse_tag(line: &str) -> Option<(&str, &str)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name, value))
+}
+
+fn parse_size(value: &str) -> Option<Size> {
+    let (x, y) = value.split_once('x')?;
+    let size = Size::new(x.parse().ok()?, y.parse().ok()?);
+    if !(1..=MAX_SIZE).contains(&size.x) || !(1..=MAX_SIZE).contains(&size.y) {
+        return None;
+    }
+    Some(size)
+}
+
+/// Parses `<number>. <player> <x>,<y>`.
+fn parse_move(text: &str, expected: usize, line: usize) -> Result<PlayerMove, RecordError> {
+    let malformed = || RecordError::at(line, "Malformed move, expected <number>. <player> <x>,<y>");
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    let (number, player, position) = match parts.as_slice() {
+        [number, player, position] => (number, player, position),
+        _ => {
+            return Err(malformed());
+        }
+    };
+    let number: usize = number
+        .strip_suffix('.')
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(malformed)?;
+    if number != expected {
+        return Err(RecordError::at(line, format!("Expected move {}, found {}", expected, number)));
+    }
+    let player = player.parse().map_err(|_| malformed())?;
+    let (x, y) = position.split_once(',').ok_or_else(malformed)?;
+    let x = x.parse().map_err(|_| malformed())?;
+    let y = y.parse().map_err(|_| malformed())?;
+    Ok(PlayerMove { player, position: Size::new(x, y) })
+}
@@ -0,0 +1,34 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::gameparameters::GameParameters;
+
+/// Content of `propose_rematch`: a new game like the finished one, with the same players.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RematchProposal {
+    /// The finished game.
+    pub game_id: u32,
+    pub parameters: GameParameters,
+    pub players: Vec<u32>,
+}
+
+/// Content of `rematch_proposed`, sent to every player of the finished game.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RematchOffer {
+    pub proposer: u32,
+    #[serde(flatten)]
+    pub proposal: RematchProposal,
+}
+
+/// Content of `rematch_response`.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct RematchResponse {
+    pub game_id: u32,
+    pub accept: bool,
+}
+
+/// Content of `rematch_declined`.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct RematchDeclined {
+    pub game_id: u32,
+    pub player: u32,
+}
@@ -0,0 +1,19 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+/// Content of the server's `error` event.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerError {
+    /// Machine readable reason, e.g. `not_your_turn`.
+    pub code: String,
+    pub message: String,
+    /// Event that caused the error, if the server knows it.
+    #[serde(default)]
+    pub request: Option<String>,
+}
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
@@ -0,0 +1,395 @@
+use std::convert::TryFrom;
+
+use crate::{
+    chat::ChatHistory,
+    encodingoptions::EncodingOptions,
+    gameinfo::GameInfo,
+    gameparameters::GameParameters,
+    gameresult::GameResult,
+    hello::{ Compatibility, Hello },
+    moveerror::MoveError,
+    grid::{ Grid, Win },
+    player::{ merge_players, set_image, Player },
+    protocol::{ ClientEvent, GameJoinData, ServerEvent },
+    record::GameRecord,
+    rematch::{ RematchOffer, RematchProposal },
+    servererror::ServerError,
+    settings::Settings,
+    utils::Size,
+};
+
+/// Client-side view of the server state, updated from `ServerEvent`s.
+///
+/// Holds no DOM or WebGL objects so the protocol flow can be exercised
+/// natively; `net` renders the result of each `apply`.
+#[derive(Default)]
+pub struct Session {
+    /// Every player seen so far, keeps their images between updates.
+    pub players: Vec<Player>,
+    /// Players from the latest `players` event.
+    pub player_list: Vec<Player>,
+    pub games: Vec<GameInfo>,
+    pub current_game: Option<GameInfo>,
+    pub board: Option<Grid>,
+    /// Whether `current_game` is only being watched.
+    pub spectating: bool,
+    /// Number of spectators of the current game.
+    pub viewers: u32,
+    /// Player whose turn it is in the current game.
+    pub turn: Option<u32>,
+    pub chat: ChatHistory,
+    /// Rematch of the finished game waiting for everyone to accept.
+    pub rematch: Option<RematchOffer>,
+    /// Line completed on the board, found before the server reports the game over.
+    pub win: Option<Win>,
+    /// Whether `board` is an imported record replayed locally, not a server game.
+    pub replaying: bool,
+    /// Local preferences, loaded at startup.
+    pub settings: Settings,
+    /// Set once the current game is over.
+    pub result: Option<GameResult>,
+    /// Encoding confirmed by the server, JSON only until it replies.
+    pub encoding: Option<EncodingOptions>,
+    /// The server's reply to our `Hello`.
+    pub server: Option<Hello>,
+}
+
+/// What changed after applying an event.
+#[derive(Debug, PartialEq)]
+pub enum Update {
+    Players,
+    Games,
+    JoinedGame,
+    GameStarted,
+    NewMove,
+    PlayerImage,
+    Encoding,
+    Hello(Compatibility),
+    Pong,
+    Error(ServerError),
+    GameOver,
+    LeftGame,
+    Watching,
+    Viewers,
+    /// A chat message for the game with this id arrived.
+    Chat(u32),
+    RematchProposed,
+    /// The player with this id declined the rematch.
+    RematchDeclined(u32),
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name of a known player.
+    pub fn player_name(&self, id: u32) -> Option<&str> {
+        self.players
+            .iter()
+            .find(|p| p.id == id)
+            .map(|p| p.name.as_str())
+    }
+
+    /// Forgets the finished board, keeping the joined game.
+    pub fn end_game(&mut self) {
+        self.board = None;
+        self.result = None;
+        self.turn = None;
+        self.win = None;
+    }
+
+    /// Whether the board is a server game seen from its first move, so that
+    /// its start is now. The server doesn't tell when a game started.
+    pub fn at_game_start(&self) -> bool {
+        !self.replaying &&
+            !self.spectating &&
+            self.board.as_ref().is_some_and(|board| board.moves().is_empty())
+    }
+
+    /// Command that gets us back into the current game on a new connection,
+    /// the server may have forgotten us with the old one. `None` outside of
+    /// server games.
+    pub fn resume_event(&self) -> Option<ClientEvent> {
+        if self.replaying {
+            return None;
+        }
+        let data = GameJoinData::new(self.current_game.as_ref()?.id);
+        Some(if self.spectating {
+            ClientEvent::WatchGame(data)
+        } else {
+            ClientEvent::JoinGame(data)
+        })
+    }
+
+    /// Forgets the joined game entirely.
+    pub fn leave_game(&mut self) {
+        if let Some(current) = &self.current_game {
+            self.chat.clear(current.id);
+        }
+        self.end_game();
+        self.current_game = None;
+        self.rematch = None;
+        self.spectating = false;
+        self.replaying = false;
+        self.viewers = 0;
+    }
+
+    /// Record of the current game, for export.
+    pub fn record(&self) -> Option<GameRecord> {
+        let info = self.current_game.as_ref()?;
+        Some(GameRecord::new(info, self.board.as_ref()?, self.result.as_ref()))
+    }
+
+    /// Shows an imported record read-only, outside of any server game.
+    pub fn load_record(&mut self, record: &GameRecord) -> Result<(), String> {
+        if self.current_game.is_some() {
+            return Err("Leave the current game before replaying a saved one".to_string());
+        }
+        self.end_game();
+        self.board = Some(record.to_grid());
+        self.spectating = true;
+        self.replaying = true;
+        Ok(())
+    }
+
+    /// Whether we may manage the current game: we host it and it has not started yet.
+    pub fn is_host(&self) -> bool {
+        let player_id = self.player_id();
+        !self.spectating &&
+            player_id.is_some() &&
+            self.current_game.as_ref().is_some_and(|g| !g.running && Some(g.creator) == player_id)
+    }
+
+    /// A rematch of the finished game with the same parameters and players.
+    pub fn rematch_proposal(&self) -> Option<RematchProposal> {
+        if self.result.is_none() || self.spectating {
+            return None;
+        }
+        let current_game = self.current_game.as_ref()?;
+        Some(RematchProposal {
+            game_id: current_game.id,
+            parameters: GameParameters::from_info(current_game),
+            players: current_game.player_list.clone(),
+        })
+    }
+
+    /// Our own player id, as told by the server's `Hello`.
+    pub fn player_id(&self) -> Option<u32> {
+        self.server.as_ref()?.player_id
+    }
+
+    /// Whether the local player may move. Assumed when the server doesn't
+    /// tell us who we are or whose turn it is.
+    pub fn can_move(&self) -> bool {
+        self.check_turn(self.player_id()).is_ok()
+    }
+
+    /// Checks `player` may play `pos`, `None` while the server hasn't told us our id.
+    pub fn validate_move(&self, player: Option<u32>, pos: &Size) -> Result<(), MoveError> {
+        let board = self.board.as_ref().ok_or(MoveError::NoGame)?;
+        self.check_turn(player)?;
+        board.check_position(pos)
+    }
+
+    fn check_turn(&self, player: Option<u32>) -> Result<(), MoveError> {
+        if self.replaying {
+            return Err(MoveError::NoGame);
+        }
+        if self.spectating {
+            return Err(MoveError::Spectating);
+        }
+        if self.result.is_some() || self.win.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        match (player, self.turn) {
+            (Some(me), Some(turn)) if me != turn => Err(MoveError::NotYourTurn(turn)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Text describing whose turn it is, `None` if unknown or the game is over.
+    pub fn turn_status(&self) -> Option<String> {
+        if self.result.is_some() || self.win.is_some() {
+            return None;
+        }
+        let turn = self.turn?;
+        let name = self.player_name(turn).unwrap_or("another player");
+        if self.spectating {
+            Some(format!("Turn of {}", name))
+        } else if self.player_id() == Some(turn) {
+            Some("Your turn".to_string())
+        } else {
+            Some(format!("Waiting for {}", name))
+        }
+    }
+
+    fn enter_game(&mut self, id: u32) -> Result<(), String> {
+        let info = self.games
+            .iter()
+            .find(|g| g.id == id)
+            .ok_or_else(|| format!("Entered unknown game {}", id))?
+            .clone();
+        // A rematch moves us straight from a finished game to a new one
+        self.end_game();
+        self.rematch = None;
+        self.replaying = false;
+        self.turn = info.current_player();
+        self.current_game = Some(info);
+        Ok(())
+    }
+
+    /// Keeps the joined game's player list up to date.
+    fn refresh_current_game(&mut self) {
+        if let Some(current) = self.current_game.as_mut() {
+            if let Some(info) = self.games.iter().find(|g| g.id == current.id) {
+                *current = info.clone();
+            }
+        }
+    }
+
+    fn win_length(&self) -> Option<u32> {
+        self.current_game.as_ref().map(|g| g.win_length)
+    }
+
+    fn in_game(&self, id: u32) -> bool {
+        self.current_game.as_ref().is_some_and(|g| g.id == id)
+    }
+
+    fn next_turn(&self, player: i32) -> Option<u32> {
+        let player = u32::try_from(player).ok()?;
+        self.current_game.as_ref()?.next_player(player)
+    }
+
+    pub fn apply(&mut self, event: ServerEvent) -> Result<Update, String> {
+        match event {
+            ServerEvent::Players(list) => {
+                merge_players(&mut self.players, &list);
+                self.player_list = list;
+                Ok(Update::Players)
+            }
+            ServerEvent::Games(games) => {
+                self.games = games;
+                self.refresh_current_game();
+                Ok(Update::Games)
+            }
+            ServerEvent::GameAdded(info) | ServerEvent::GameUpdated(info) => {
+                match self.games.iter_mut().find(|g| g.id == info.id) {
+                    Some(game) => {
+                        *game = info;
+                    }
+                    None => self.games.push(info),
+                }
+                self.refresh_current_game();
+                Ok(Update::Games)
+            }
+            ServerEvent::GameRemoved(data) => {
+                self.games.retain(|g| g.id != data.id);
+                // Nothing left to be in, the server won't send `left_game` for it
+                if self.current_game.as_ref().is_some_and(|g| g.id == data.id) {
+                    self.leave_game();
+                    return Ok(Update::LeftGame);
+                }
+                Ok(Update::Games)
+            }
+            ServerEvent::JoinedGame(data) => {
+                self.enter_game(data.id)?;
+                self.spectating = false;
+                Ok(Update::JoinedGame)
+            }
+            ServerEvent::Watching(viewers) => {
+                self.enter_game(viewers.id)?;
+                self.spectating = true;
+                self.viewers = viewers.viewers;
+                Ok(Update::Watching)
+            }
+            ServerEvent::Viewers(viewers) => {
+                match &self.current_game {
+                    Some(current) if current.id == viewers.id => {}
+                    _ => {
+                        return Err(format!("Viewer count for unknown game {}", viewers.id));
+                    }
+                }
+                self.viewers = viewers.viewers;
+                Ok(Update::Viewers)
+            }
+            ServerEvent::Chat(message) => {
+                let game_id = message.game_id;
+                self.chat.push(message);
+                Ok(Update::Chat(game_id))
+            }
+            ServerEvent::RematchProposed(offer) => {
+                if !self.in_game(offer.proposal.game_id) {
+                    return Err(format!("Rematch of unknown game {}", offer.proposal.game_id));
+                }
+                self.rematch = Some(offer);
+                Ok(Update::RematchProposed)
+            }
+            ServerEvent::RematchDeclined(declined) => {
+                if !self.in_game(declined.game_id) {
+                    return Err(format!("Rematch of unknown game {}", declined.game_id));
+                }
+                self.rematch = None;
+                Ok(Update::RematchDeclined(declined.player))
+            }
+            ServerEvent::CurrentState(grid) => {
+                self.turn = match grid.moves().last() {
+                    Some(last) => self.next_turn(last.player),
+                    None => self.current_game.as_ref().and_then(|g| g.current_player()),
+                };
+                self.win = self.win_length().and_then(|length| grid.check_win(length));
+                self.board = Some(grid);
+                self.result = None;
+                Ok(Update::GameStarted)
+            }
+            ServerEvent::NewMove(m) => {
+                let win_length = self.win_length();
+                let board = self.board
+                    .as_mut()
+                    .ok_or_else(|| "Received a move outside of a game".to_string())?;
+                let player = m.player;
+                let position = m.position;
+                board.add(m);
+                if self.win.is_none() {
+                    self.win = win_length.and_then(|length| board.check_win_at(&position, length));
+                }
+                self.turn = self.next_turn(player);
+                Ok(Update::NewMove)
+            }
+            ServerEvent::PlayerImage(response) => {
+                set_image(&mut self.players, response);
+                Ok(Update::PlayerImage)
+            }
+            ServerEvent::Encoding(options) => {
+                self.encoding = Some(options);
+                Ok(Update::Encoding)
+            }
+            ServerEvent::Hello(hello) => {
+                let compatibility = Hello::client().compatibility(&hello);
+                self.server = Some(hello);
+                Ok(Update::Hello(compatibility))
+            }
+            // Round trips are timed by the connection, nothing to store
+            ServerEvent::Pong(_) => Ok(Update::Pong),
+            ServerEvent::Error(err) => Ok(Update::Error(err)),
+            ServerEvent::GameOver(result) => {
+                if self.board.is_none() {
+                    return Err("Received a game result outside of a game".to_string());
+                }
+                self.result = Some(result);
+                self.turn = None;
+                Ok(Update::GameOver)
+            }
+            ServerEvent::LeftGame(data) => {
+                match &self.current_game {
+                    Some(current) if current.id == data.id => {}
+                    _ => {
+                        return Err(format!("Left game {} without being in it", data.id));
+                    }
+                }
+                self.leave_game();
+                Ok(Update::LeftGame)
+            }
+        }
+    }
+}
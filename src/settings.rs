@@ -0,0 +1,120 @@
+use std::fmt;
+
+use serde::{ Deserialize, Serialize };
+
+use crate::utils::window;
+
+/// Longest display name accepted, in characters.
+pub const MAX_NAME_LENGTH: usize = 32;
+/// Local storage key holding the serialized settings.
+const STORAGE_KEY: &str = "settings";
+
+/// Content of `set_name`.
+#[derive(Serialize, Clone, Debug)]
+pub struct PlayerName {
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NameError {
+    Empty,
+    TooLong,
+    InvalidCharacter,
+}
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameError::Empty => write!(f, "Name is empty"),
+            NameError::TooLong => write!(f, "Name is longer than {} characters", MAX_NAME_LENGTH),
+            NameError::InvalidCharacter => write!(f, "Name contains invalid characters"),
+        }
+    }
+}
+
+/// Trims the name and checks the server would accept it.
+pub fn validate_name(name: &str) -> Result<String, NameError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(NameError::TooLong);
+    }
+    if name.chars().any(char::is_control) {
+        return Err(NameError::InvalidCharacter);
+    }
+    Ok(name.to_string())
+}
+
+/// Parses a `#rrggbb` color into its components in the 0-1 range.
+pub fn parse_color(color: &str) -> Option<[f32; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let mut rgb = [0.0; 3];
+    for (i, component) in rgb.iter_mut().enumerate() {
+        let value = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        *component = f32::from(value) / 255.0;
+    }
+    Some(rgb)
+}
+
+/// Preferences of the local player, kept in the browser's local storage.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Tint of the local player's tiles, as `#rrggbb`.
+    pub symbol_color: String,
+    pub show_latency: bool,
+    pub show_chat: bool,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            symbol_color: "#3080ff".to_string(),
+            show_latency: true,
+            show_chat: true,
+        }
+    }
+}
+impl Settings {
+    /// Parses stored settings, falling back to the defaults when they are unreadable.
+    pub fn from_json(text: &str) -> Self {
+        serde_json::from_str(text).unwrap_or_default()
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Unable to serialize settings")
+    }
+    pub fn symbol_rgb(&self) -> [f32; 3] {
+        parse_color(&self.symbol_color)
+            .or_else(|| parse_color(&Settings::default().symbol_color))
+            .unwrap()
+    }
+    /// Classes set on `body` to hide the parts of the UI turned off.
+    pub fn body_class(&self) -> String {
+        let mut classes = Vec::new();
+        if !self.show_latency {
+            classes.push("hide-latency");
+        }
+        if !self.show_chat {
+            classes.push("hide-chat");
+        }
+        classes.join(" ")
+    }
+
+    pub fn load() -> Self {
+        let stored = window()
+            .local_storage()
+            .ok()
+            .flatten()
+            .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten());
+        stored.map_or_else(Settings::default, |text| Settings::from_json(&text))
+    }
+    pub fn save(&self) {
+        if let Some(storage) = window().local_storage().ok().flatten() {
+            // Settings are only a convenience, a full or disabled storage is not an error
+            let _ = storage.set_item(STORAGE_KEY, &self.to_json());
+        }
+    }
+}
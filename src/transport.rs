@@ -0,0 +1,132 @@
+use std::{ cell::{ Cell, RefCell }, collections::VecDeque, rc::Rc };
+
+use crate::{
+    channel::Channel,
+    pendingrequests::{ OnReply, Reply },
+    protocol::{ ClientEvent, ServerEvent, ServerMessage },
+};
+
+/// Way to talk to the server, a shell around the `Channel` that holds the
+/// protocol state.
+pub trait Transport {
+    fn channel(&self) -> &RefCell<Channel>;
+    /// Current time in milliseconds.
+    fn now(&self) -> f64;
+    /// Called with every finished request before its callback runs.
+    fn on_reply(&self, _reply: &Reply) {}
+
+    /// Sends an encoded message, or queues it until the transport is open.
+    fn send_text(&self, text: String) {
+        let replies = self.channel().borrow_mut().send(text, self.now());
+        self.run_replies(replies);
+    }
+    fn is_open(&self) -> bool {
+        self.channel().borrow().is_open()
+    }
+    fn send(&self, event: &ClientEvent) {
+        self.send_text(event.encode());
+    }
+    /// Sends a command with a request id. `on_reply` is called once with the
+    /// server's answer, or when it doesn't answer in time. Servers that don't
+    /// echo request ids get the plain command and `on_reply` gets `Sent`.
+    fn request(&self, event: &ClientEvent, on_reply: OnReply) {
+        let replies = self.channel().borrow_mut().request(event, on_reply, self.now());
+        self.run_replies(replies);
+    }
+    /// Handles a received message, returns the event if it is left for the
+    /// session.
+    fn dispatch(&self, message: ServerMessage) -> Option<ServerEvent> {
+        let received = self.channel().borrow_mut().receive(&message, self.now());
+        self.run_replies(received.replies);
+        if received.forward {
+            Some(message.event)
+        } else {
+            None
+        }
+    }
+    /// Runs the callbacks of finished requests, once the channel is free for
+    /// them to send more.
+    fn run_replies(&self, replies: Vec<Reply>) {
+        for reply in replies {
+            self.on_reply(&reply);
+            reply.run();
+        }
+    }
+}
+
+/// In-memory transport connecting the client to a server in the same process.
+///
+/// Clones share the same buffers: the client side uses the `Transport`
+/// methods and `receive`, the server side uses `server_receive` and
+/// `server_send`. Time only passes for requests and heartbeat pings when
+/// calling `advance`. Missing too many pongs closes the transport.
+#[derive(Clone)]
+pub struct LoopbackTransport {
+    channel: Rc<RefCell<Channel>>,
+    to_server: Rc<RefCell<VecDeque<String>>>,
+    to_client: Rc<RefCell<VecDeque<String>>>,
+    now: Rc<Cell<f64>>,
+}
+
+impl LoopbackTransport {
+    pub fn new() -> Self {
+        let to_server = Rc::new(RefCell::new(VecDeque::new()));
+        let writer = to_server.clone();
+        let mut channel = Channel::new(
+            Box::new(move |text: &str| {
+                writer.borrow_mut().push_back(text.to_string());
+                Ok(())
+            })
+        );
+        channel.opened(&[], 0.0);
+        Self {
+            channel: Rc::new(RefCell::new(channel)),
+            to_server,
+            to_client: Rc::new(RefCell::new(VecDeque::new())),
+            now: Rc::new(Cell::new(0.0)),
+        }
+    }
+    /// Takes the next message sent by the server.
+    pub fn receive(&self) -> Option<String> {
+        self.to_client.borrow_mut().pop_front()
+    }
+    /// Takes the next message sent by the client.
+    pub fn server_receive(&self) -> Option<String> {
+        self.to_server.borrow_mut().pop_front()
+    }
+    /// Delivers a message to the client.
+    pub fn server_send(&self, text: String) {
+        self.to_client.borrow_mut().push_back(text);
+    }
+    /// Moves the clock forward, timing out requests that went unanswered and
+    /// sending the heartbeat pings that came due on the way.
+    pub fn advance(&self, ms: f64) {
+        let until = self.now.get() + ms;
+        loop {
+            let next_ping = self.channel.borrow().next_ping().filter(|&at| at < until);
+            self.now.set(next_ping.unwrap_or(until));
+            let tick = self.channel.borrow_mut().tick(self.now.get());
+            self.run_replies(tick.replies);
+            if next_ping.is_none() || tick.dead {
+                break;
+            }
+        }
+        self.now.set(until);
+    }
+    pub fn pending_requests(&self) -> usize {
+        self.channel.borrow().pending_requests()
+    }
+}
+impl Default for LoopbackTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Transport for LoopbackTransport {
+    fn channel(&self) -> &RefCell<Channel> {
+        &self.channel
+    }
+    fn now(&self) -> f64 {
+        self.now.get()
+    }
+}
@@ -1,7 +1,18 @@
-use js_sys::JSON;
 use serde::{ Serialize, Deserialize };
-use wasm_bindgen::{ JsCast, closure::Closure, JsValue };
-use web_sys::{ Document, Element, Event, HtmlCollection, HtmlElement, HtmlInputElement, Window };
+use wasm_bindgen::{ JsCast, JsValue, closure::Closure };
+use web_sys::{
+    Blob,
+    BlobPropertyBag,
+    Document,
+    Element,
+    Event,
+    HtmlAnchorElement,
+    HtmlCollection,
+    HtmlElement,
+    HtmlInputElement,
+    Url,
+    Window,
+};
 
 extern crate web_sys;
 
@@ -70,24 +81,7 @@ impl Size {
             y: y,
         }
     }
-    pub fn to_json(&self) -> String {
-        (*self).into()
-    }
-}
-impl From<Size> for String {
-    fn from(value: Size) -> Self {
-        serde_wasm_bindgen
-            ::from_value(
-                JSON::stringify(
-                    &serde_wasm_bindgen::to_value(&value).expect("Could not convert to JsValue")
-                )
-                    .expect("Could not stringify")
-                    .into()
-            )
-            .expect("Could not convert from JsValue")
-    }
 }
-
 pub fn players_div() -> HtmlElement {
     get_element_by_id("player-list")
 }
@@ -105,6 +99,15 @@ pub fn set_timeout(f: &Closure<dyn FnMut()>, interval_ms: i32) -> i32 {
         .expect("should register `setTimeout` OK")
 }
 
+pub fn set_interval(f: &Closure<dyn FnMut()>, interval_ms: i32) -> i32 {
+    window()
+        .set_interval_with_callback_and_timeout_and_arguments_0(
+            f.as_ref().unchecked_ref(),
+            interval_ms
+        )
+        .expect("should register `setInterval` OK")
+}
+
 pub fn get_element_by_id(id: &str) -> HtmlElement {
     document()
         .get_element_by_id(id)
@@ -120,6 +123,33 @@ pub fn get_elements_by_class_name(name: &str) -> HtmlCollection {
     document().get_elements_by_class_name(name)
 }
 
+/// Saves `text` to a file through the browser's download.
+pub fn download(filename: &str, text: &str) {
+    let options = BlobPropertyBag::new();
+    options.set_type("text/plain");
+    let parts = js_sys::Array::of1(&JsValue::from_str(text));
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options).expect(
+        "Unable to create blob"
+    );
+    let url = Url::create_object_url_with_blob(&blob).expect("Unable to create object URL");
+
+    let link: HtmlAnchorElement = document()
+        .create_element("a")
+        .expect("Unable to create link")
+        .dyn_into()
+        .expect("Not HtmlAnchorElement type");
+    link.set_href(&url);
+    link.set_download(filename);
+    link.click();
+    // The download only starts after this tick, it needs the URL until then
+    let revoke = Closure::once_into_js(move || {
+        let _ = Url::revoke_object_url(&url);
+    });
+    window()
+        .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 1000)
+        .expect("should register `setTimeout` OK");
+}
+
 pub fn add_event_listener(element: &Element, event: &str, f: impl Fn(Event) + 'static) {
     let cb = Closure::wrap(Box::new(f) as Box<dyn FnMut(_)>);
     element
@@ -128,22 +158,30 @@ pub fn add_event_listener(element: &Element, event: &str, f: impl Fn(Event) + 's
     cb.forget();
 }
 
-pub fn from_jsvalue<T>(value: JsValue) -> Result<T, String> where T: serde::de::DeserializeOwned {
-    let result: Result<T, serde_wasm_bindgen::Error> = serde_wasm_bindgen::from_value(value);
-    if result.is_ok() {
-        return Ok(result.unwrap());
+/// Event listener that is removed from its element when dropped.
+pub struct EventListener {
+    element: Element,
+    event: &'static str,
+    callback: Closure<dyn FnMut(Event)>,
+}
+impl EventListener {
+    pub fn new(element: &Element, event: &'static str, f: impl Fn(Event) + 'static) -> Self {
+        let callback = Closure::wrap(Box::new(f) as Box<dyn FnMut(_)>);
+        element
+            .add_event_listener_with_callback(event, callback.as_ref().unchecked_ref())
+            .expect("Something went wrong");
+        Self {
+            element: element.clone(),
+            event,
+            callback,
+        }
     }
-    let err_string = result.err().unwrap().to_string();
-    Err(err_string)
 }
-
-pub fn from_json<T>(text: &str) -> Result<T, String> where T: serde::de::DeserializeOwned {
-    let result: Result<T, serde_wasm_bindgen::Error> = serde_wasm_bindgen::from_value(
-        JSON::parse(text).expect("Unable to parse")
-    );
-    if result.is_ok() {
-        return Ok(result.unwrap());
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        let _ = self.element.remove_event_listener_with_callback(
+            self.event,
+            self.callback.as_ref().unchecked_ref()
+        );
     }
-    let err_string = result.err().unwrap().to_string();
-    Err(err_string)
 }
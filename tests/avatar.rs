@@ -0,0 +1,25 @@
+use tictactoe_client::{
+    avatar::{ crop_square, png_base64, Crop },
+    protocol::{ AvatarUpload, ClientEvent },
+};
+
+#[test]
+fn crop_square_test() {
+    assert_eq!(crop_square(300, 200), Some(Crop { x: 50, y: 0, side: 200 }));
+    assert_eq!(crop_square(16, 41), Some(Crop { x: 0, y: 12, side: 16 }));
+    assert_eq!(crop_square(0, 10), None);
+}
+
+#[test]
+fn upload_test() {
+    assert_eq!(png_base64("data:image/png;base64,iVBORw0K"), Some("iVBORw0K"));
+    assert_eq!(png_base64("data:image/jpeg;base64,/9j/"), None);
+    // Canvases that can't be encoded yield "data:,"
+    assert_eq!(png_base64("data:,"), None);
+
+    let upload = ClientEvent::SetImage(AvatarUpload { image: "iVBORw0K".to_string() });
+    assert_eq!(
+        upload.encode(),
+        r#"{"event":"set_image","content":"{\"image\":\"iVBORw0K\"}"}"#
+    );
+}
@@ -0,0 +1,83 @@
+use tictactoe_client::{
+    binary::{ decode, encode_grid, encode_move, encode_players, BINARY_VERSION },
+    protocol::{ Grid, Player, PlayerMove, ProtocolError, ServerEvent, ServerMessage, Size },
+};
+
+fn player_move(player: i32, x: i32, y: i32) -> PlayerMove {
+    PlayerMove { player, position: Size::new(x, y) }
+}
+
+#[test]
+fn move_round_trip_test() {
+    match decode(&encode_move(&player_move(3, -200, 70000), Some(41))) {
+        Ok(ServerMessage { request_id: Some(41), event: ServerEvent::NewMove(m) }) => {
+            assert_eq!(m.player, 3);
+            assert_eq!(m.position, Size::new(-200, 70000));
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn grid_round_trip_test() {
+    let moves = (0..100).map(|i| player_move(i % 4, i, -i)).collect();
+    let bytes = encode_grid(&Grid::new(Size::new(30, 40), moves), None);
+
+    match decode(&bytes) {
+        Ok(ServerMessage { request_id: None, event: ServerEvent::CurrentState(grid) }) => {
+            assert_eq!(grid.size, Size::new(30, 40));
+            assert_eq!(grid.moves().len(), 100);
+            assert_eq!(grid.moves()[99].player, 3);
+            assert_eq!(grid.moves()[99].position, Size::new(99, -99));
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    // A move is at most a few bytes instead of ~40 in JSON
+    assert!(bytes.len() < 100 * 6);
+}
+
+#[test]
+fn players_round_trip_test() {
+    let players = vec![
+        Player::new(1, "Alice".to_string(), None, false),
+        Player::new(2, "Bøb".to_string(), Some(0), true)
+    ];
+
+    match decode(&encode_players(&players, None)) {
+        Ok(ServerMessage { event: ServerEvent::Players(decoded), .. }) => {
+            assert_eq!(decoded.len(), 2);
+            assert_eq!(decoded[0].name, "Alice");
+            assert_eq!(decoded[0].joined_game_id, None);
+            assert!(!decoded[0].ready);
+            assert_eq!(decoded[1].name, "Bøb");
+            assert_eq!(decoded[1].joined_game_id, Some(0));
+            assert!(decoded[1].ready);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_errors_test() {
+    let bytes = encode_move(&player_move(1, 2, 3), None);
+
+    assert!(matches!(decode(&[]), Err(ProtocolError::InvalidBinary(_))));
+    assert!(matches!(decode(&[BINARY_VERSION + 1, 1, 0]), Err(ProtocolError::InvalidBinary(_))));
+    assert!(matches!(decode(&[BINARY_VERSION, 42, 0]), Err(ProtocolError::UnknownEvent(_))));
+    assert!(
+        matches!(decode(&bytes[..bytes.len() - 1]), Err(ProtocolError::InvalidBinary(_)))
+    );
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(decode(&trailing), Err(ProtocolError::InvalidBinary(_))));
+
+    // Claims a million players in a six byte message
+    let bytes = [BINARY_VERSION, 3, 0, 0xc0, 0x84, 0x3d];
+    assert!(matches!(decode(&bytes), Err(ProtocolError::InvalidBinary(_))));
+
+    // A length of 2^32 must not wrap around to zero players
+    let bytes = [BINARY_VERSION, 3, 0, 0x80, 0x80, 0x80, 0x80, 0x10];
+    assert!(matches!(decode(&bytes), Err(ProtocolError::InvalidBinary(_))));
+}
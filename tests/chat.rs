@@ -0,0 +1,50 @@
+mod common;
+
+use common::{ frame, join_game, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    chat::{ prepare_message, ChatError, RateLimiter, MAX_LENGTH },
+    session::{ Session, Update },
+    transport::LoopbackTransport,
+};
+
+#[test]
+fn history_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    join_game(&server, &mut session, 7, &[1], 3, 3);
+    server.push(frame("chat", json!({ "game_id": 7, "player": 1, "text": "gl hf" })));
+    server.push(frame("chat", json!({ "game_id": 8, "player": 2, "text": "hi" })));
+    server.push(frame("chat", json!({ "game_id": 7, "player": 2, "text": "you too" })));
+    let updates = pump(&transport, &mut session);
+    assert_eq!(updates, &[Ok(Update::Chat(7)), Ok(Update::Chat(8)), Ok(Update::Chat(7))]);
+
+    let texts: Vec<&str> = session.chat
+        .messages(7)
+        .map(|m| m.text.as_str())
+        .collect();
+    assert_eq!(texts, vec!["gl hf", "you too"]);
+    assert_eq!(session.chat.messages(9).count(), 0);
+
+    server.push(frame("left_game", json!({ "id": 7 })));
+    pump(&transport, &mut session);
+    assert_eq!(session.chat.messages(7).count(), 0);
+    assert_eq!(session.chat.messages(8).count(), 1);
+}
+
+#[test]
+fn prepare_message_test() {
+    let mut limiter = RateLimiter::new(2, 1000.0);
+
+    assert_eq!(prepare_message("   ", &mut limiter, 0.0), Err(ChatError::Empty));
+    assert_eq!(
+        prepare_message(&"a".repeat(MAX_LENGTH + 1), &mut limiter, 0.0),
+        Err(ChatError::TooLong)
+    );
+    assert_eq!(prepare_message(" hello ", &mut limiter, 0.0), Ok("hello".to_string()));
+    assert_eq!(prepare_message("again", &mut limiter, 200.0), Ok("again".to_string()));
+    assert_eq!(prepare_message("spam", &mut limiter, 400.0), Err(ChatError::RateLimited(600.0)));
+    assert_eq!(prepare_message("later", &mut limiter, 1000.0), Ok("later".to_string()));
+}
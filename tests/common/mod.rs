@@ -0,0 +1,126 @@
+//! Scriptable fake server for driving the client protocol natively.
+
+// Each test crate only uses part of the helpers
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use serde_json::{ json, Value };
+use tictactoe_client::{
+    protocol::ServerMessage,
+    session::{ Session, Update },
+    transport::{ LoopbackTransport, Transport },
+};
+
+type Handler = Box<dyn FnMut(&str) -> Vec<String>>;
+
+pub struct FakeServer {
+    transport: LoopbackTransport,
+    handlers: HashMap<String, Handler>,
+    /// Events received from the client, in order.
+    pub received: Vec<(String, String)>,
+}
+impl FakeServer {
+    pub fn new(transport: &LoopbackTransport) -> Self {
+        Self {
+            transport: transport.clone(),
+            handlers: HashMap::new(),
+            received: Vec::new(),
+        }
+    }
+    /// Replies to every `event` sent by the client with the frames returned by `handler`.
+    ///
+    /// If the client sent a request, the first frame is marked as its reply.
+    pub fn on(&mut self, event: &str, handler: impl FnMut(&str) -> Vec<String> + 'static) {
+        self.handlers.insert(event.to_string(), Box::new(handler));
+    }
+    /// Sends a frame to the client without waiting for a request.
+    pub fn push(&self, frame: String) {
+        self.transport.server_send(frame);
+    }
+    /// Handles every message the client has sent so far.
+    pub fn process(&mut self) {
+        while let Some(text) = self.transport.server_receive() {
+            let message: Value = serde_json::from_str(&text).expect("Client sent invalid JSON");
+            let event = message["event"].as_str().expect("Missing event").to_string();
+            let content = message["content"].as_str().expect("Missing content").to_string();
+
+            if let Some(handler) = self.handlers.get_mut(&event) {
+                let mut replies = handler(&content);
+                if let (Some(id), Some(first)) = (message.get("request_id"), replies.first_mut()) {
+                    let mut frame: Value = serde_json::from_str(first).unwrap();
+                    frame["request_id"] = id.clone();
+                    *first = frame.to_string();
+                }
+                for reply in replies {
+                    self.transport.server_send(reply);
+                }
+            }
+            self.received.push((event, content));
+        }
+    }
+}
+
+/// Joins game `id` and starts it on an empty board, applying everything the
+/// server sent so far.
+pub fn join_game(
+    server: &FakeServer,
+    session: &mut Session,
+    id: u32,
+    player_list: &[u32],
+    width: u32,
+    height: u32
+) {
+    server.push(frame("games", json!([game_info(id, player_list, width, height)])));
+    server.push(frame("joined_game", json!({ "id": id })));
+    let size = json!({ "x": width, "y": height });
+    server.push(frame("current_state", json!({ "size": size, "moves": [] })));
+    for update in pump(&server.transport, session) {
+        update.expect("Unable to join game");
+    }
+}
+
+/// Encodes a server frame the way the real server does.
+pub fn frame(event: &str, content: Value) -> String {
+    json!({ "event": event, "content": content.to_string() }).to_string()
+}
+
+/// Dispatches everything the server sent like the connection does, applying
+/// what is left for the session.
+pub fn pump(transport: &LoopbackTransport, session: &mut Session) -> Vec<Result<Update, String>> {
+    let mut updates = Vec::new();
+    while let Some(text) = transport.receive() {
+        let message = ServerMessage::decode(&text).expect("Server sent an invalid event");
+        if let Some(event) = transport.dispatch(message) {
+            updates.push(session.apply(event));
+        }
+    }
+    updates
+}
+
+/// The server's `hello`, listing the optional features it supports.
+pub fn hello(features: &[&str]) -> String {
+    frame(
+        "hello",
+        json!({ "version": "1.0.0", "protocol": 1, "min_protocol": 1, "features": features })
+    )
+}
+
+pub fn player(id: u32, name: &str, joined_game_id: Option<u32>) -> Value {
+    json!({ "id": id, "name": name, "joined_game_id": joined_game_id, "ready": false })
+}
+
+pub fn game_info(id: u32, player_list: &[u32], width: u32, height: u32) -> Value {
+    json!({
+        "id": id,
+        "player_list": player_list,
+        "creator": player_list.first().copied().unwrap_or(0),
+        "current_turn": 0,
+        "hotjoin": true,
+        "player_limit": 100,
+        "running": false,
+        "win_length": 3,
+        "width": width,
+        "height": height,
+    })
+}
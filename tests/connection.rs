@@ -0,0 +1,21 @@
+use tictactoe_client::connection::Backoff;
+
+#[test]
+fn backoff_test() {
+    let mut backoff = Backoff::new(500, 4000);
+
+    assert_eq!(backoff.next_delay(), 500);
+    assert_eq!(backoff.next_delay(), 1000);
+    assert_eq!(backoff.next_delay(), 2000);
+    assert_eq!(backoff.next_delay(), 4000);
+    assert_eq!(backoff.next_delay(), 4000);
+    assert_eq!(backoff.attempts(), 5);
+
+    for _ in 0..100 {
+        assert_eq!(backoff.next_delay(), 4000);
+    }
+
+    backoff.reset();
+    assert_eq!(backoff.attempts(), 0);
+    assert_eq!(backoff.next_delay(), 500);
+}
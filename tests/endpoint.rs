@@ -0,0 +1,50 @@
+use tictactoe_client::endpoint::{ resolve, scheme_for, validate, EndpointError };
+
+#[test]
+fn scheme_test() {
+    assert_eq!(scheme_for("https:"), "wss");
+    assert_eq!(scheme_for("http:"), "ws");
+    assert_eq!(scheme_for("file:"), "ws");
+}
+
+#[test]
+fn resolve_test() {
+    assert_eq!(resolve(None, "http:", "localhost").unwrap(), "ws://localhost:9001/");
+    assert_eq!(resolve(None, "https:", "example.com").unwrap(), "wss://example.com:9001/");
+    assert_eq!(resolve(None, "http:", "localhost:8080").unwrap(), "ws://localhost:9001/");
+    assert_eq!(resolve(None, "http:", "[::1]:8080").unwrap(), "ws://[::1]:9001/");
+    // Pages opened from disk have no host to fall back to
+    assert_eq!(resolve(None, "file:", ""), Err(EndpointError::MissingHost));
+    assert_eq!(
+        resolve(Some("/tictactoe/ws"), "https:", "example.com:8443").unwrap(),
+        "wss://example.com:8443/tictactoe/ws"
+    );
+    assert_eq!(
+        resolve(Some("game.example.com:8443"), "https:", "example.com").unwrap(),
+        "wss://game.example.com:8443/"
+    );
+    assert_eq!(
+        resolve(Some("ws://10.0.0.2:9001/"), "https:", "example.com").unwrap(),
+        "ws://10.0.0.2:9001/"
+    );
+    assert_eq!(resolve(Some("ws://[::1]:9001"), "http:", "").unwrap(), "ws://[::1]:9001/");
+}
+
+#[test]
+fn validate_errors_test() {
+    assert_eq!(
+        validate("http://example.com/"),
+        Err(EndpointError::UnsupportedScheme("http".to_string()))
+    );
+    assert_eq!(validate("ws:///path"), Err(EndpointError::MissingHost));
+    assert_eq!(
+        validate("ws://exa mple.com/"),
+        Err(EndpointError::InvalidHost("exa mple.com".to_string()))
+    );
+    assert_eq!(
+        validate("ws://example.com:99999/"),
+        Err(EndpointError::InvalidPort("99999".to_string()))
+    );
+    assert_eq!(validate("ws://example.com:/"), Err(EndpointError::InvalidPort("".to_string())));
+    assert_eq!(resolve(Some(""), "http:", "localhost"), Err(EndpointError::MissingHost));
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{ frame, game_info, join_game, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    gamelist::GameList,
+    protocol::GameInfo,
+    session::{ Session, Update },
+    transport::LoopbackTransport,
+};
+
+fn games(list: &[(u32, usize)]) -> Vec<GameInfo> {
+    list.iter()
+        .map(|&(id, players)| {
+            let players: Vec<u32> = (1..=players as u32).collect();
+            serde_json::from_value(game_info(id, &players, 3, 3)).unwrap()
+        })
+        .collect()
+}
+
+/// Applies `games` and returns the patches made, views record what created them.
+fn sync(list: &mut GameList<String>, games: &[GameInfo]) -> Vec<String> {
+    let mut patches = Vec::new();
+    let mut moves = Vec::new();
+    let mut updates = Vec::new();
+    let mut removed = Vec::new();
+    list.sync(
+        games,
+        |g, next| {
+            patches.push(format!("add {} before {:?}", g.id, next));
+            format!("game {}", g.id)
+        },
+        |g, _| updates.push(format!("update {}", g.id)),
+        |view, next| moves.push(format!("move {} before {:?}", view, next)),
+        |view| removed.push(format!("remove {}", view))
+    );
+    patches.extend(moves);
+    patches.extend(updates);
+    patches.extend(removed);
+    patches
+}
+
+#[test]
+fn sync_test() {
+    let mut list = GameList::new();
+    assert_eq!(sync(&mut list, &games(&[(1, 1), (2, 1)])), vec![
+        "add 2 before None",
+        "add 1 before Some(\"game 2\")",
+    ]);
+
+    // Unchanged entries are left alone
+    assert!(sync(&mut list, &games(&[(1, 1), (2, 1)])).is_empty());
+
+    assert_eq!(sync(&mut list, &games(&[(1, 2), (3, 1), (2, 1)])), vec![
+        "add 3 before Some(\"game 2\")",
+        "update 1",
+    ]);
+    // Entries the server moved follow it, new ones land before the moved entry
+    assert_eq!(sync(&mut list, &games(&[(4, 1), (2, 1), (1, 2), (3, 1)])), vec![
+        "add 4 before Some(\"game 2\")",
+        "move game 2 before Some(\"game 1\")",
+    ]);
+    assert_eq!(sync(&mut list, &games(&[(3, 1)])), vec![
+        "remove game 4",
+        "remove game 2",
+        "remove game 1",
+    ]);
+    assert_eq!(list.len(), 1);
+    assert_eq!(list.view(3), Some(&"game 3".to_string()));
+}
+
+#[test]
+fn delta_events_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    join_game(&server, &mut session, 7, &[1], 3, 3);
+    server.push(frame("game_added", game_info(8, &[2], 5, 5)));
+    server.push(frame("game_updated", game_info(7, &[1, 3], 3, 3)));
+    server.push(frame("game_removed", json!({ "id": 8 })));
+    let updates = pump(&transport, &mut session);
+    assert_eq!(updates, &[Ok(Update::Games), Ok(Update::Games), Ok(Update::Games)]);
+
+    assert_eq!(session.games.len(), 1);
+    assert_eq!(session.current_game.as_ref().unwrap().player_list, vec![1, 3]);
+
+    // Removing the joined game takes the player out of it
+    server.push(frame("game_removed", json!({ "id": 7 })));
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::LeftGame)]);
+    assert!(session.games.is_empty());
+    assert!(session.current_game.is_none() && session.board.is_none());
+}
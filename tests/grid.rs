@@ -0,0 +1,109 @@
+mod common;
+
+use common::{ frame, join_game, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    grid::{ Grid, Win },
+    protocol::{ PlayerMove, Size },
+    session::Session,
+    transport::LoopbackTransport,
+};
+
+fn grid(width: i32, height: i32, moves: &[(i32, i32, i32)]) -> Grid {
+    let mut grid = Grid::new(Size::new(width, height), Vec::new());
+    for &(player, x, y) in moves {
+        grid.add(PlayerMove { player, position: Size::new(x, y) });
+    }
+    grid
+}
+
+fn cells(list: &[(i32, i32)]) -> Vec<Size> {
+    list.iter()
+        .map(|&(x, y)| Size::new(x, y))
+        .collect()
+}
+
+#[test]
+fn check_win_test() {
+    let board = grid(5, 5, &[(1, 0, 0), (2, 0, 1), (1, 1, 1), (2, 0, 2), (1, 2, 2)]);
+    let win = Win { player: 1, cells: cells(&[(0, 0), (1, 1), (2, 2)]) };
+    assert_eq!(board.check_win(3), Some(win));
+    assert_eq!(board.check_win(4), None);
+    assert_eq!(board.check_win(0), None);
+
+    // Anti-diagonal ending on the board's edge
+    let board = grid(3, 3, &[(2, 0, 2), (2, 1, 1), (2, 2, 0)]);
+    assert_eq!(board.check_win(3).unwrap().cells, cells(&[(0, 2), (1, 1), (2, 0)]));
+
+    // Lines don't wrap around rows
+    let board = grid(3, 3, &[(1, 1, 0), (1, 2, 0), (1, 0, 1)]);
+    assert_eq!(board.check_win(3), None);
+}
+
+#[test]
+fn check_win_at_test() {
+    let mut board = grid(10, 10, &[(1, 3, 5), (1, 4, 5), (1, 6, 5), (1, 7, 5), (2, 2, 5)]);
+    assert_eq!(board.check_win_at(&Size::new(4, 5), 4), None);
+
+    // Filling the gap joins both halves
+    board.add(PlayerMove { player: 1, position: Size::new(5, 5) });
+    let win = board.check_win_at(&Size::new(5, 5), 4).unwrap();
+    assert_eq!(win.player, 1);
+    assert_eq!(win.cells, cells(&[(3, 5), (4, 5), (5, 5), (6, 5), (7, 5)]));
+
+    assert_eq!(board.check_win_at(&Size::new(9, 9), 1), None);
+    assert_eq!(board.check_win_at(&Size::new(-1, 5), 1), None);
+}
+
+#[test]
+fn large_board_test() {
+    // A 500 tiles long diagonal, checked after every move
+    let mut board = grid(500, 500, &[]);
+    for i in 0..500 {
+        board.add(PlayerMove { player: 1, position: Size::new(i, 499 - i) });
+        let win = board.check_win_at(&Size::new(i, 499 - i), 500);
+        assert_eq!(win.is_some(), i == 499);
+    }
+    assert_eq!(board.check_win(500).unwrap().cells.len(), 500);
+}
+
+#[test]
+fn session_win_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    join_game(&server, &mut session, 7, &[1, 2], 3, 3);
+    for (player, x, y) in [(1, 0, 0), (2, 0, 1), (1, 1, 0), (2, 1, 1)] {
+        let content = json!({ "player": player, "position": { "x": x, "y": y } });
+        server.push(frame("new_move", content));
+    }
+    pump(&transport, &mut session);
+    assert!(session.win.is_none() && session.can_move());
+
+    server.push(frame("new_move", json!({ "player": 1, "position": { "x": 2, "y": 0 } })));
+    pump(&transport, &mut session);
+    assert_eq!(session.win.as_ref().unwrap().cells, cells(&[(0, 0), (1, 0), (2, 0)]));
+    assert!(!session.can_move());
+    assert_eq!(session.turn_status(), None);
+}
+
+#[test]
+fn storage_test() {
+    let json = r#"{"size": {"x": 3, "y": 2}, "moves": [
+        {"player": 1, "position": {"x": 2, "y": 1}},
+        {"player": 2, "position": {"x": 5, "y": 0}},
+        {"player": 3, "position": {"x": 2, "y": 1}}
+    ]}"#;
+    let board: Grid = serde_json::from_str(json).unwrap();
+    // The last move on a tile owns it, moves outside the board are only logged
+    assert_eq!(board.get_pos(&Size::new(2, 1)), Some(3));
+    assert_eq!(board.get_pos(&Size::new(5, 0)), None);
+    assert_eq!(board.moves().len(), 3);
+
+    assert_eq!(board.index(&Size::new(2, 1)), Some(5));
+    assert_eq!(board.index(&Size::new(3, 0)), None);
+    assert!(board.is_valid_move(&Size::new(0, 0)));
+    assert!(!board.is_valid_move(&Size::new(2, 1)));
+    assert!(!board.is_valid_move(&Size::new(-1, 0)));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{ frame, hello, pump, FakeServer };
+use tictactoe_client::{
+    heartbeat::{ Heartbeat, Ping, Quality },
+    session::Session,
+    transport::{ LoopbackTransport, Transport },
+};
+
+#[test]
+fn round_trip_test() {
+    let mut heartbeat = Heartbeat::new(1000, 2);
+    assert_eq!(heartbeat.latency_ms(), None);
+    assert_eq!(heartbeat.quality(), None);
+
+    let ping = heartbeat.ping(0.0);
+    assert_eq!(heartbeat.pong(&ping, 40.0), Some(40.0));
+    // Duplicate pong
+    assert_eq!(heartbeat.pong(&ping, 50.0), None);
+
+    let ping = heartbeat.ping(1000.0);
+    assert_eq!(heartbeat.pong(&ping, 1160.0), Some(160.0));
+    assert_eq!(heartbeat.latency_ms(), Some(100.0));
+    assert_eq!(heartbeat.quality(), Some(Quality::Good));
+
+    let ping = heartbeat.ping(2000.0);
+    heartbeat.pong(&ping, 2600.0);
+    assert_eq!(heartbeat.quality(), Some(Quality::Fair));
+}
+
+#[test]
+fn missed_pong_test() {
+    let mut heartbeat = Heartbeat::new(1000, 2);
+    let first = heartbeat.ping(0.0);
+    heartbeat.pong(&first, 10.0);
+
+    let late = heartbeat.ping(1000.0);
+    heartbeat.ping(2000.0);
+    assert_eq!(heartbeat.missed(), 1);
+    assert!(!heartbeat.is_dead());
+    assert_eq!(heartbeat.quality(), Some(Quality::Poor));
+
+    // The pong for an older ping doesn't count
+    assert_eq!(heartbeat.pong(&late, 2050.0), None);
+    heartbeat.ping(3000.0);
+    assert!(heartbeat.is_dead());
+
+    heartbeat.reset();
+    assert!(!heartbeat.is_dead());
+    assert_eq!(heartbeat.pong(&Ping { id: 3 }, 3010.0), None);
+}
+
+#[test]
+fn heartbeat_feature_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    // A server without heartbeat support never gets pinged, so it can't miss pongs
+    server.push(hello(&[]));
+    pump(&transport, &mut session);
+    transport.advance(60000.0);
+    server.process();
+    assert!(server.received.is_empty());
+    assert!(transport.is_open());
+
+    server.on("ping", |content| vec![frame("pong", serde_json::from_str(content).unwrap())]);
+    server.push(hello(&["heartbeat"]));
+    pump(&transport, &mut session);
+    for _ in 0..4 {
+        transport.advance(5000.0);
+        server.process();
+        pump(&transport, &mut session);
+    }
+    assert_eq!(server.received.len(), 4);
+    assert!(transport.is_open());
+
+    // Pings left unanswered end the connection
+    server.on("ping", |_| Vec::new());
+    transport.advance(15000.0);
+    assert!(!transport.is_open());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{ frame, hello, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    hello::{ Compatibility, Hello, PROTOCOL_VERSION },
+    protocol::ClientEvent,
+    session::{ Session, Update },
+    transport::{ LoopbackTransport, Transport },
+};
+
+fn server_hello(protocol: u32, min_protocol: u32) -> Hello {
+    Hello {
+        version: "9.9.9".to_string(),
+        protocol,
+        min_protocol,
+        features: Vec::new(),
+        player_id: None,
+    }
+}
+
+#[test]
+fn compatibility_test() {
+    let client = Hello::client();
+    assert_eq!(client.compatibility(&client), Compatibility::Compatible);
+    assert_eq!(
+        client.compatibility(&server_hello(PROTOCOL_VERSION + 1, PROTOCOL_VERSION)),
+        Compatibility::Compatible
+    );
+    assert_eq!(
+        client.compatibility(&server_hello(PROTOCOL_VERSION + 2, PROTOCOL_VERSION + 1)),
+        Compatibility::ClientOutdated
+    );
+    assert_eq!(
+        client.compatibility(&server_hello(client.min_protocol - 1, 0)),
+        Compatibility::ServerOutdated
+    );
+    assert!(client.supports("binary"));
+}
+
+#[test]
+fn handshake_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    server.on("hello", |content| {
+        let hello: Hello = serde_json::from_str(content).unwrap();
+        vec![
+            frame(
+                "hello",
+                json!({ "version": "2.0.0", "protocol": hello.protocol + 1, "min_protocol": 99 })
+            )
+        ]
+    });
+
+    transport.send(&ClientEvent::Hello(Hello::client()));
+    server.process();
+    assert_eq!(
+        pump(&transport, &mut session),
+        vec![Ok(Update::Hello(Compatibility::ClientOutdated))]
+    );
+    assert_eq!(session.server.unwrap().version, "2.0.0");
+}
+
+#[test]
+fn binary_feature_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    // Only servers that list it are asked for binary frames
+    server.push(hello(&["request_id"]));
+    pump(&transport, &mut session);
+    server.process();
+    assert!(server.received.is_empty());
+
+    server.push(hello(&["binary"]));
+    pump(&transport, &mut session);
+    server.process();
+    assert_eq!(server.received.len(), 1);
+    assert_eq!(server.received[0].0, "encoding");
+}
@@ -0,0 +1,50 @@
+use tictactoe_client::{
+    grid::Grid,
+    history::{ History, Step },
+    protocol::{ PlayerMove, Size },
+};
+
+#[test]
+fn step_test() {
+    let mut history = History::new();
+    assert!(history.is_live());
+    assert_eq!(history.ply(5), 5);
+
+    history.step(Step::Back, 5);
+    assert_eq!(history.ply(5), 4);
+    // New moves don't move the ply shown
+    assert_eq!(history.ply(7), 4);
+
+    history.step(Step::First, 7);
+    history.step(Step::Back, 7);
+    assert_eq!(history.ply(7), 0);
+
+    history.step(Step::Jump(6), 7);
+    history.step(Step::Forward, 7);
+    assert!(history.is_live());
+
+    history.step(Step::Jump(2), 7);
+    history.step(Step::Live, 7);
+    assert!(history.is_live());
+    history.step(Step::Jump(100), 7);
+    assert!(history.is_live());
+
+    // An empty board is always live
+    history.step(Step::First, 0);
+    assert!(history.is_live());
+}
+
+#[test]
+fn at_ply_test() {
+    let moves = (0..4)
+        .map(|i| PlayerMove { player: i % 2, position: Size::new(i, 0) })
+        .collect();
+    let board = Grid::new(Size::new(4, 4), moves);
+
+    let past = board.at_ply(2);
+    assert_eq!(past.moves().len(), 2);
+    assert_eq!(past.get_pos(&Size::new(1, 0)), Some(1));
+    assert_eq!(past.get_pos(&Size::new(2, 0)), None);
+    assert_eq!(board.at_ply(10).moves().len(), 4);
+    assert_eq!(board.at_ply(0).get_pos(&Size::new(0, 0)), None);
+}
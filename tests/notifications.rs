@@ -0,0 +1,32 @@
+use tictactoe_client::{
+    notifications::{ failure_message, server_error_message },
+    pendingrequests::Outcome,
+    protocol::{ ServerEvent, ServerMessage },
+};
+
+#[test]
+fn messages_test() {
+    let text =
+        r#"{"event":"error","content":"{\"code\":\"full\",\"message\":\"Game is full\",\"request\":\"join_game\"}"}"#;
+    let err = match ServerMessage::decode(text) {
+        Ok(ServerMessage { request_id: None, event: ServerEvent::Error(err) }) => err,
+        other => panic!("Unexpected result: {:?}", other),
+    };
+    assert_eq!(err.request.as_deref(), Some("join_game"));
+    assert_eq!(server_error_message(&err), "Server refused join_game: Game is full");
+
+    assert_eq!(failure_message("Joining game", &Outcome::Accepted), None);
+    assert_eq!(
+        failure_message("Joining game", &Outcome::Rejected(err)).unwrap(),
+        "Joining game failed: Game is full"
+    );
+    assert_eq!(
+        failure_message("Move", &Outcome::TimedOut).unwrap(),
+        "Move failed: the server did not respond"
+    );
+    assert_eq!(
+        failure_message("Move", &Outcome::Disconnected).unwrap(),
+        "Move failed: the connection was lost"
+    );
+    assert_eq!(failure_message("Move", &Outcome::Sent), None);
+}
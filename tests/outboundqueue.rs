@@ -0,0 +1,38 @@
+use tictactoe_client::outboundqueue::{ DropPolicy, OutboundQueue };
+
+#[test]
+fn fifo_order_test() {
+    let mut queue = OutboundQueue::new(4, DropPolicy::DropOldest);
+    for i in 0..3 {
+        assert_eq!(queue.push(i.to_string()), None);
+    }
+    assert_eq!(queue.len(), 3);
+
+    assert_eq!(queue.pop(), Some("0".to_string()));
+    queue.requeue("0".to_string());
+    assert_eq!(queue.pop(), Some("0".to_string()));
+    assert_eq!(queue.pop(), Some("1".to_string()));
+    assert_eq!(queue.pop(), Some("2".to_string()));
+    assert_eq!(queue.pop(), None);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn drop_policy_test() {
+    let mut queue = OutboundQueue::new(2, DropPolicy::DropOldest);
+    queue.push("a".to_string());
+    queue.push("b".to_string());
+    assert_eq!(queue.push("c".to_string()), Some("a".to_string()));
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.dropped(), 1);
+    assert_eq!(queue.pop(), Some("b".to_string()));
+    assert_eq!(queue.pop(), Some("c".to_string()));
+
+    let mut queue = OutboundQueue::new(2, DropPolicy::DropNewest);
+    queue.push("a".to_string());
+    queue.push("b".to_string());
+    assert_eq!(queue.push("c".to_string()), Some("c".to_string()));
+    assert_eq!(queue.dropped(), 1);
+    assert_eq!(queue.pop(), Some("a".to_string()));
+    assert_eq!(queue.pop(), Some("b".to_string()));
+}
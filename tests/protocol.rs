@@ -0,0 +1,78 @@
+use tictactoe_client::protocol::{ ClientEvent, ProtocolError, ServerEvent, ServerMessage };
+
+#[test]
+fn decode_server_event_test() {
+    let text =
+        r#"{"event":"new_move","content":"{\"player\":2,\"position\":{\"x\":3,\"y\":4}}"}"#;
+
+    match ServerEvent::decode(text) {
+        Ok(ServerEvent::NewMove(m)) => {
+            assert_eq!(m.player, 2);
+            assert_eq!(m.position.x, 3);
+            assert_eq!(m.position.y, 4);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    let text =
+        r#"{"event":"players","content":"[{\"id\":1,\"joined_game_id\":null,\"ready\":false,\"name\":\"Bob\"}]"}"#;
+
+    match ServerEvent::decode(text) {
+        Ok(ServerEvent::Players(players)) => {
+            assert_eq!(players.len(), 1);
+            assert_eq!(players[0].name, "Bob");
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_errors_test() {
+    assert!(matches!(ServerEvent::decode("not json"), Err(ProtocolError::InvalidFrame(_))));
+    assert_eq!(
+        ServerEvent::decode(r#"{"event":"dance","content":""}"#).unwrap_err(),
+        ProtocolError::UnknownEvent("dance".to_string())
+    );
+    assert!(
+        matches!(
+            ServerEvent::decode(r#"{"event":"joined_game","content":"{\"game\":1}"}"#),
+            Err(ProtocolError::MalformedContent { event, .. }) if event == "joined_game"
+        )
+    );
+    // Every listed name is a real event, so bad content is never taken for an unknown event
+    for name in ServerEvent::NAMES {
+        let text = serde_json::json!({ "event": name, "content": "{" }).to_string();
+        assert!(
+            matches!(ServerEvent::decode(&text), Err(ProtocolError::MalformedContent { .. })),
+            "{} is not a server event",
+            name
+        );
+    }
+}
+
+#[test]
+fn encode_client_event_test() {
+    assert_eq!(ClientEvent::Players.encode(), r#"{"event":"players","content":""}"#);
+    assert_eq!(
+        ClientEvent::AddBot("minmax".to_string()).encode(),
+        r#"{"event":"add_bot","content":"minmax"}"#
+    );
+    assert_eq!(ClientEvent::GetImage(5).encode(), r#"{"event":"get_image","content":"5"}"#);
+}
+
+#[test]
+fn request_id_test() {
+    let text = ClientEvent::Ready.encode_request(12);
+    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(value["event"], "ready");
+    assert_eq!(value["request_id"], 12);
+
+    let text =
+        r#"{"event":"error","content":"{\"code\":\"full\",\"message\":\"Game is full\"}","request_id":12}"#;
+    match ServerMessage::decode(text) {
+        Ok(ServerMessage { request_id: Some(12), event: ServerEvent::Error(err) }) => {
+            assert_eq!(err.code, "full");
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
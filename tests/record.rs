@@ -0,0 +1,105 @@
+mod common;
+
+use common::{ frame, join_game, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    moveerror::MoveError,
+    protocol::{ PlayerMove, Size },
+    record::{ GameRecord, RecordError, RecordResult },
+    session::Session,
+    transport::LoopbackTransport,
+};
+
+const RECORD: &str = "[Game \"7\"]
+[Size \"3x3\"]
+[WinLength \"3\"]
+[Hotjoin \"true\"]
+[Players \"1 2\"]
+[Result \"1\"]
+
+1. 1 0,0
+2. 2 1,0
+3. 1 0,1
+4. 2 1,1
+5. 1 0,2
+";
+
+#[test]
+fn export_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+    assert!(session.record().is_none());
+
+    join_game(&server, &mut session, 7, &[1, 2], 3, 3);
+    for (player, x, y) in [(1, 0, 0), (2, 1, 0), (1, 0, 1), (2, 1, 1), (1, 0, 2)] {
+        let content = json!({ "player": player, "position": { "x": x, "y": y } });
+        server.push(frame("new_move", content));
+    }
+    server.push(frame("game_over", json!({ "winner": 1 })));
+    pump(&transport, &mut session);
+
+    let record = session.record().unwrap();
+    assert_eq!(record.result, RecordResult::Winner(1));
+    assert_eq!(record.to_string(), RECORD);
+    assert_eq!(GameRecord::parse(RECORD), Ok(record));
+}
+
+#[test]
+fn parse_test() {
+    let text = "; Saved by hand\n[Size \"4x2\"]\n[WinLength \"2\"]\n[Players \"5 6\"]\n\
+        [Event \"ignored\"]\n\n1. 5 3,1\n2. 6 0,0\n";
+    let record = GameRecord::parse(text).unwrap();
+    assert_eq!(record.game_id, None);
+    assert_eq!(record.size, Size::new(4, 2));
+    assert!(!record.hotjoin);
+    assert_eq!(record.result, RecordResult::Unfinished);
+    assert_eq!(record.moves[0], PlayerMove { player: 5, position: Size::new(3, 1) });
+    assert_eq!(record.to_grid().get_pos(&Size::new(0, 0)), Some(6));
+
+    let error = |text: &str| GameRecord::parse(text).unwrap_err();
+    let header = "[Size \"3x3\"]\n[WinLength \"3\"]\n[Players \"1 2\"]\n";
+    assert_eq!(
+        error("[Size \"3x3\"]\n[Players \"1 2\"]\n"),
+        RecordError { line: None, message: "Missing WinLength tag".to_string() }
+    );
+    assert_eq!(error("[Size \"0x3\"]\n").line, Some(1));
+    assert_eq!(error("[Size \"3x65536\"]\n").message, "Invalid Size \"3x65536\"");
+    assert_eq!(error(&format!("{}1. 1 0,0\n3. 2 1,1\n", header)).line, Some(5));
+    assert_eq!(error(&format!("{}1. 3 0,0\n", header)).to_string(), "Line 4: Unknown player 3");
+    assert_eq!(
+        error(&format!("{}1. 1 0,0\n2. 2 0,0\n", header)).to_string(),
+        format!("Line 5: {}", MoveError::Occupied(Size::new(0, 0)))
+    );
+    assert_eq!(
+        error(&format!("{}1. 1 3,0\n", header)).message,
+        MoveError::OutOfBounds(Size::new(3, 0)).to_string()
+    );
+    assert_eq!(error(&format!("{}1. 1 0,0\n[Result \"*\"]\n", header)).line, Some(5));
+}
+
+#[test]
+fn load_record_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+    let record = GameRecord::parse(RECORD).unwrap();
+
+    session.load_record(&record).unwrap();
+    assert!(session.replaying);
+    assert_eq!(session.board.as_ref().unwrap().moves().len(), 5);
+    // Replays are read-only
+    assert_eq!(session.validate_move(Some(1), &Size::new(2, 2)), Err(MoveError::NoGame));
+    assert!(session.record().is_none());
+    // Nothing to rejoin after a reconnect
+    assert!(session.resume_event().is_none());
+
+    // Joining a server game ends the replay
+    join_game(&server, &mut session, 8, &[1, 2], 3, 3);
+    assert!(!session.replaying);
+    assert!(session.board.as_ref().unwrap().moves().is_empty());
+    assert!(session.load_record(&record).is_err());
+
+    session.leave_game();
+    assert!(session.load_record(&record).is_ok());
+}
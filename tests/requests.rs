@@ -0,0 +1,154 @@
+mod common;
+
+use std::{ cell::RefCell, rc::Rc };
+
+use common::{ frame, hello, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    channel::Channel,
+    hello::Hello,
+    pendingrequests::{ OnReply, Outcome, PendingRequests },
+    protocol::{ ClientEvent, Size },
+    session::{ Session, Update },
+    transport::{ LoopbackTransport, Transport },
+};
+
+fn record(outcomes: &Rc<RefCell<Vec<Outcome>>>) -> OnReply {
+    let outcomes = outcomes.clone();
+    Box::new(move |outcome| outcomes.borrow_mut().push(outcome))
+}
+
+#[test]
+fn reply_and_rejection_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+    let outcomes = Rc::new(RefCell::new(Vec::new()));
+
+    server.push(hello(&["request_id"]));
+    let board = frame("current_state", json!({ "size": { "x": 3, "y": 3 }, "moves": [] }));
+    server.push(board);
+    pump(&transport, &mut session);
+
+    let mut taken = Vec::new();
+    server.on("move", move |content| {
+        let position: serde_json::Value = serde_json::from_str(content).unwrap();
+        if taken.contains(&position) {
+            return vec![
+                frame("error", json!({ "code": "invalid_move", "message": "Tile is taken" }))
+            ];
+        }
+        taken.push(position.clone());
+        vec![frame("new_move", json!({ "player": 1, "position": position }))]
+    });
+
+    transport.request(&ClientEvent::Move(Size::new(1, 1)), record(&outcomes));
+    transport.request(&ClientEvent::Move(Size::new(1, 1)), record(&outcomes));
+    // Plain messages don't get a request id
+    transport.send(&ClientEvent::Players);
+    assert_eq!(transport.pending_requests(), 2);
+
+    server.process();
+    // The rejection goes to its request's callback, not to the session
+    let updates = pump(&transport, &mut session);
+    assert_eq!(updates, vec![Ok(Update::NewMove)]);
+    assert_eq!(transport.pending_requests(), 0);
+
+    let outcomes = outcomes.borrow();
+    assert_eq!(outcomes[0], Outcome::Accepted);
+    assert!(matches!(&outcomes[1], Outcome::Rejected(err) if err.message == "Tile is taken"));
+}
+
+#[test]
+fn timeout_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+    let outcomes = Rc::new(RefCell::new(Vec::new()));
+    server.push(hello(&["request_id"]));
+    pump(&transport, &mut session);
+
+    transport.request(&ClientEvent::Ready, record(&outcomes));
+    server.process();
+    assert_eq!(server.received[0].0, "ready");
+
+    transport.advance(9999.0);
+    assert!(outcomes.borrow().is_empty());
+    transport.advance(1.0);
+    assert_eq!(*outcomes.borrow(), vec![Outcome::TimedOut]);
+    assert_eq!(transport.pending_requests(), 0);
+}
+
+#[test]
+fn untracked_request_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+    let outcomes = Rc::new(RefCell::new(Vec::new()));
+
+    // Without `request_id` in its hello the server would never echo the id
+    server.push(hello(&[]));
+    pump(&transport, &mut session);
+    transport.request(&ClientEvent::Ready, record(&outcomes));
+    assert_eq!(transport.pending_requests(), 0);
+    assert_eq!(*outcomes.borrow(), vec![Outcome::Sent]);
+    assert_eq!(transport.server_receive(), Some(ClientEvent::Ready.encode()));
+
+    transport.advance(20000.0);
+    assert_eq!(outcomes.borrow().len(), 1);
+}
+
+#[test]
+fn queued_request_test() {
+    let mut requests = PendingRequests::new(1000.0);
+    let outcomes = Rc::new(RefCell::new(Vec::new()));
+    let queued = requests.insert("move".to_string(), record(&outcomes));
+    let written = requests.insert("ready".to_string(), record(&outcomes));
+
+    // The timeout only starts once the request is written
+    requests.sent(written, 0.0);
+    let expired = requests.expire(5000.0);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].name, "ready");
+    assert_eq!(requests.len(), 1);
+
+    // Losing the socket fails what it was sent, queued requests wait for the next one
+    requests.sent(queued, 5000.0);
+    let other = requests.insert("join_game".to_string(), record(&outcomes));
+    let lost = requests.disconnected();
+    assert_eq!(lost.len(), 1);
+    assert_eq!(lost[0].name, "move");
+    assert_eq!(lost[0].outcome, Outcome::Disconnected);
+    assert!(requests.fail(other, Outcome::Disconnected).is_some());
+    assert!(requests.is_empty());
+}
+
+#[test]
+fn closed_channel_test() {
+    let written = Rc::new(RefCell::new(Vec::new()));
+    let writer = written.clone();
+    let mut channel = Channel::new(
+        Box::new(move |text: &str| {
+            writer.borrow_mut().push(text.to_string());
+            Ok(())
+        })
+    );
+    let outcomes = Rc::new(RefCell::new(Vec::new()));
+    channel.opened(&[], 0.0);
+    channel.server_hello(&Hello::client(), 0.0);
+    written.borrow_mut().clear();
+
+    // Requests made while closed wait for the next socket, after its handshake
+    assert!(channel.closed().is_empty());
+    assert!(channel.request(&ClientEvent::Ready, record(&outcomes), 0.0).is_empty());
+    assert!(channel.tick(20000.0).replies.is_empty());
+    assert!(written.borrow().is_empty());
+
+    assert!(channel.opened(&[ClientEvent::Players], 30000.0).is_empty());
+    assert_eq!(written.borrow()[0], ClientEvent::Players.encode());
+    assert_eq!(written.borrow()[1], ClientEvent::Ready.encode_request(1));
+    let expired = channel.tick(40000.0).replies;
+    assert_eq!(expired.len(), 1);
+    expired.into_iter().for_each(|reply| reply.run());
+    assert_eq!(*outcomes.borrow(), vec![Outcome::TimedOut]);
+}
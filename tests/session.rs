@@ -0,0 +1,363 @@
+mod common;
+
+use std::{ cell::RefCell, rc::Rc };
+
+use common::{ frame, game_info, hello, join_game, player, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    gameresult::format_duration,
+    moveerror::MoveError,
+    pendingrequests::Outcome,
+    protocol::{
+        ClientEvent,
+        GameJoinData,
+        GameParameters,
+        GameUpdate,
+        PlayerAction,
+        RematchResponse,
+        Size,
+    },
+    session::{ Session, Update },
+    transport::{ LoopbackTransport, Transport },
+};
+
+#[test]
+fn join_and_play_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    server.on("players", |_| {
+        vec![frame("players", json!([player(1, "Alice", None), player(2, "Bob", None)]))]
+    });
+    server.on("games", |_| { vec![frame("games", json!([game_info(7, &[2], 3, 3)]))] });
+    server.on("join_game", |content| {
+        let id = serde_json::from_str::<serde_json::Value>(content).unwrap()["id"].clone();
+        vec![
+            frame("joined_game", json!({ "id": id })),
+            frame("players", json!([player(1, "Alice", Some(7)), player(2, "Bob", Some(7))]))
+        ]
+    });
+    server.on("ready", |_| {
+        vec![frame("current_state", json!({ "size": { "x": 3, "y": 3 }, "moves": [] }))]
+    });
+    server.on("move", |content| {
+        let position: serde_json::Value = serde_json::from_str(content).unwrap();
+        vec![frame("new_move", json!({ "player": 1, "position": position }))]
+    });
+
+    transport.send(&ClientEvent::Players);
+    transport.send(&ClientEvent::Games);
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::Players), Ok(Update::Games)]);
+    assert_eq!(session.player_list.len(), 2);
+    assert_eq!(session.games[0].id, 7);
+    assert!(session.current_game.is_none());
+
+    transport.send(&ClientEvent::JoinGame(GameJoinData::new(7)));
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::JoinedGame), Ok(Update::Players)]);
+    assert_eq!(session.current_game.as_ref().unwrap().id, 7);
+    assert!(session.player_list.iter().all(|p| p.joined_game_id == Some(7)));
+
+    transport.send(&ClientEvent::Ready);
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::GameStarted)]);
+    assert_eq!(session.board.as_ref().unwrap().size, Size::new(3, 3));
+    assert!(session.at_game_start());
+
+    transport.send(&ClientEvent::Move(Size::new(1, 2)));
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::NewMove)]);
+    // Joined again after this, the game would have started before
+    assert!(!session.at_game_start());
+
+    let board = session.board.as_mut().unwrap();
+    assert_eq!(board.get_pos(&Size::new(1, 2)), Some(1));
+    assert_eq!(board.get_pos(&Size::new(2, 1)), None);
+
+    let events: Vec<&str> = server.received
+        .iter()
+        .map(|(event, _)| event.as_str())
+        .collect();
+    assert_eq!(events, vec!["players", "games", "join_game", "ready", "move"]);
+}
+
+#[test]
+fn unsolicited_events_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    // Moves and joins that don't match the client state are rejected
+    server.push(frame("new_move", json!({ "player": 1, "position": { "x": 0, "y": 0 } })));
+    server.push(frame("joined_game", json!({ "id": 3 })));
+    let updates = pump(&transport, &mut session);
+    assert!(updates.iter().all(|u| u.is_err()));
+
+    server.push(frame("players", json!([player(4, "Carol", None)])));
+    server.push(frame("player_image", json!({ "id": 4, "image": "iVBORw0KGgo=" })));
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::Players), Ok(Update::PlayerImage)]);
+    assert_eq!(session.players[0].image_data(), Some("iVBORw0KGgo="));
+}
+
+#[test]
+fn validate_move_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+    assert_eq!(session.validate_move(Some(1), &Size::new(0, 0)), Err(MoveError::NoGame));
+
+    join_game(&server, &mut session, 7, &[1, 2], 3, 3);
+    server.push(frame("new_move", json!({ "player": 1, "position": { "x": 1, "y": 1 } })));
+    pump(&transport, &mut session);
+
+    assert_eq!(session.validate_move(Some(2), &Size::new(0, 0)), Ok(()));
+    assert_eq!(session.validate_move(Some(1), &Size::new(0, 0)), Err(MoveError::NotYourTurn(2)));
+    // Unknown own id, only the board is checked
+    assert_eq!(session.validate_move(None, &Size::new(0, 0)), Ok(()));
+    let occupied = session.validate_move(Some(2), &Size::new(1, 1));
+    assert_eq!(occupied, Err(MoveError::Occupied(Size::new(1, 1))));
+    // Neither wraps around to another tile nor panics
+    for pos in [Size::new(-1, 1), Size::new(3, 0), Size::new(0, -3), Size::new(0, 3)] {
+        assert_eq!(session.validate_move(Some(2), &pos), Err(MoveError::OutOfBounds(pos)));
+    }
+    assert_eq!(MoveError::OutOfBounds(Size::new(3, 0)).to_string(), "3x0 is outside the board");
+
+    server.push(frame("game_over", json!({ "winner": 1 })));
+    pump(&transport, &mut session);
+    assert_eq!(session.validate_move(Some(2), &Size::new(0, 0)), Err(MoveError::GameOver));
+}
+
+#[test]
+fn game_over_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    let result = json!({ "winner": 2, "winning_cells": [{ "x": 0, "y": 0 }, { "x": 1, "y": 1 }] });
+    server.push(frame("game_over", result.clone()));
+    assert!(pump(&transport, &mut session)[0].is_err());
+
+    server.push(frame("players", json!([player(2, "Bob", Some(7))])));
+    server.push(frame("current_state", json!({ "size": { "x": 2, "y": 2 }, "moves": [] })));
+    server.push(frame("game_over", result));
+    assert_eq!(
+        pump(&transport, &mut session),
+        vec![Ok(Update::Players), Ok(Update::GameStarted), Ok(Update::GameOver)]
+    );
+
+    let result = session.result.as_ref().unwrap();
+    assert!(!result.is_draw());
+    assert_eq!(session.player_name(result.winner.unwrap()), Some("Bob"));
+    assert_eq!(result.winning_cells, vec![Size::new(0, 0), Size::new(1, 1)]);
+
+    server.push(frame("game_over", json!({ "winner": null, "draw": true })));
+    pump(&transport, &mut session);
+    assert!(session.result.as_ref().unwrap().is_draw());
+
+    session.end_game();
+    assert!(session.board.is_none() && session.result.is_none());
+
+    assert_eq!(format_duration(65400.0), "1:05");
+    assert_eq!(format_duration(3723000.0), "1:02:03");
+}
+
+#[test]
+fn turn_test() {
+    let transport = LoopbackTransport::new();
+    let server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    // Without knowing who we are every turn is allowed
+    assert!(session.can_move());
+
+    let hello = json!({ "version": "1.0.0", "protocol": 1, "min_protocol": 1, "player_id": 1 });
+    server.push(frame("hello", hello));
+    server.push(frame("players", json!([player(1, "Alice", None), player(2, "Bob", None)])));
+    join_game(&server, &mut session, 7, &[1, 2], 3, 3);
+    assert_eq!(session.player_id(), Some(1));
+    assert_eq!(session.turn, Some(1));
+    assert!(session.can_move());
+    assert_eq!(session.turn_status().as_deref(), Some("Your turn"));
+
+    server.push(frame("new_move", json!({ "player": 1, "position": { "x": 0, "y": 0 } })));
+    pump(&transport, &mut session);
+    assert_eq!(session.turn, Some(2));
+    assert!(!session.can_move());
+    assert_eq!(session.turn_status().as_deref(), Some("Waiting for Bob"));
+
+    server.push(frame("new_move", json!({ "player": 2, "position": { "x": 1, "y": 0 } })));
+    pump(&transport, &mut session);
+    assert_eq!(session.turn, Some(1));
+
+    // A snapshot after reconnecting continues after the last move
+    let moves = json!([{ "player": 1, "position": { "x": 2, "y": 2 } }]);
+    server.push(frame("current_state", json!({ "size": { "x": 3, "y": 3 }, "moves": moves })));
+    pump(&transport, &mut session);
+    assert_eq!(session.turn, Some(2));
+
+    let mut info = session.current_game.clone().unwrap();
+    info.current_turn = 3;
+    assert_eq!(info.current_player(), Some(2));
+    assert_eq!(info.next_player(2), Some(1));
+    assert_eq!(info.next_player(5), None);
+}
+
+#[test]
+fn leave_game_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    server.on("leave_game", |_| { vec![frame("left_game", json!({ "id": 7 }))] });
+
+    server.push(hello(&["request_id"]));
+    assert!(session.resume_event().is_none());
+    join_game(&server, &mut session, 7, &[1], 3, 3);
+    assert!(session.board.is_some());
+    let rejoin = ClientEvent::JoinGame(GameJoinData::new(7)).encode();
+    assert_eq!(session.resume_event().map(|event| event.encode()), Some(rejoin));
+
+    let outcome = Rc::new(RefCell::new(None));
+    let outcome_clone = outcome.clone();
+    transport.request(
+        &ClientEvent::LeaveGame,
+        Box::new(move |o| {
+            *outcome_clone.borrow_mut() = Some(o);
+        })
+    );
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::LeftGame)]);
+    assert_eq!(*outcome.borrow(), Some(Outcome::Accepted));
+    assert!(session.current_game.is_none() && session.board.is_none() && session.turn.is_none());
+
+    // Not in a game anymore
+    server.push(frame("left_game", json!({ "id": 7 })));
+    assert!(pump(&transport, &mut session)[0].is_err());
+}
+
+#[test]
+fn rematch_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    server.on("propose_rematch", |content| {
+        let mut offer: serde_json::Value = serde_json::from_str(content).unwrap();
+        offer["proposer"] = json!(2);
+        vec![frame("rematch_proposed", offer)]
+    });
+    server.on("rematch_response", |_| {
+        vec![
+            frame("games", json!([game_info(7, &[1, 2], 3, 3), game_info(8, &[1, 2], 3, 3)])),
+            frame("joined_game", json!({ "id": 8 }))
+        ]
+    });
+
+    join_game(&server, &mut session, 7, &[1, 2], 3, 3);
+    assert!(session.rematch_proposal().is_none());
+
+    server.push(frame("game_over", json!({ "winner": 1 })));
+    pump(&transport, &mut session);
+    let proposal = session.rematch_proposal().unwrap();
+    assert_eq!(proposal.game_id, 7);
+    assert_eq!(proposal.players, vec![1, 2]);
+    assert_eq!(proposal.parameters, GameParameters::new(Size::new(3, 3), true, 100, 3));
+
+    transport.send(&ClientEvent::ProposeRematch(proposal));
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::RematchProposed)]);
+    assert_eq!(session.rematch.as_ref().unwrap().proposer, 2);
+
+    transport.send(&ClientEvent::RematchResponse(RematchResponse { game_id: 7, accept: true }));
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::Games), Ok(Update::JoinedGame)]);
+    assert_eq!(session.current_game.as_ref().unwrap().id, 8);
+    assert!(session.rematch.is_none() && session.result.is_none() && session.board.is_none());
+
+    // Offers for another game are rejected
+    server.push(frame("rematch_declined", json!({ "game_id": 7, "player": 1 })));
+    assert!(pump(&transport, &mut session)[0].is_err());
+}
+
+#[test]
+fn host_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    server.on("transfer_host", |content| {
+        let action: serde_json::Value = serde_json::from_str(content).unwrap();
+        let mut info = game_info(7, &[1, 2], 3, 3);
+        info["creator"] = action["player"].clone();
+        vec![frame("games", json!([info]))]
+    });
+    server.on("update_game", |content| {
+        let update: serde_json::Value = serde_json::from_str(content).unwrap();
+        assert_eq!(update["parameters"]["size"], json!({ "x": 5, "y": 4 }));
+        vec![frame("games", json!([game_info(7, &[1, 2], 5, 4)]))]
+    });
+
+    let hello = json!({ "version": "1.0.0", "protocol": 1, "min_protocol": 1, "player_id": 1 });
+    server.push(frame("hello", hello));
+    join_game(&server, &mut session, 7, &[1, 2], 3, 3);
+    assert!(session.is_host());
+
+    let parameters = GameParameters::new(Size::new(5, 4), true, 100, 3);
+    transport.send(&ClientEvent::UpdateGame(GameUpdate { game_id: 7, parameters }));
+    server.process();
+    pump(&transport, &mut session);
+    assert_eq!(session.current_game.as_ref().unwrap().width, 5);
+
+    transport.send(&ClientEvent::TransferHost(PlayerAction { game_id: 7, player: 2 }));
+    server.process();
+    pump(&transport, &mut session);
+    assert!(!session.is_host());
+
+    let kick = ClientEvent::KickPlayer(PlayerAction { game_id: 7, player: 2 });
+    assert_eq!(kick.name(), "kick_player");
+    assert_eq!(ClientEvent::StartGame(GameJoinData::new(7)).name(), "start_game");
+}
+
+#[test]
+fn spectator_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    server.on("watch_game", |_| {
+        vec![
+            frame("watching", json!({ "id": 7, "viewers": 1 })),
+            frame("current_state", json!({ "size": { "x": 3, "y": 3 }, "moves": [] }))
+        ]
+    });
+
+    let hello = json!({ "version": "1.0.0", "protocol": 1, "min_protocol": 1, "player_id": 3 });
+    server.push(frame("hello", hello));
+    server.push(frame("players", json!([player(1, "Alice", Some(7)), player(2, "Bob", Some(7))])));
+    server.push(frame("games", json!([game_info(7, &[1, 2], 3, 3)])));
+    pump(&transport, &mut session);
+
+    transport.send(&ClientEvent::WatchGame(GameJoinData::new(7)));
+    server.process();
+    assert_eq!(pump(&transport, &mut session), vec![Ok(Update::Watching), Ok(Update::GameStarted)]);
+    assert!(session.spectating);
+    assert!(!session.can_move());
+    assert!(!session.at_game_start());
+    let rewatch = ClientEvent::WatchGame(GameJoinData::new(7)).encode();
+    assert_eq!(session.resume_event().map(|event| event.encode()), Some(rewatch));
+    assert_eq!(session.turn_status().as_deref(), Some("Turn of Alice"));
+
+    server.push(frame("viewers", json!({ "id": 7, "viewers": 4 })));
+    server.push(frame("viewers", json!({ "id": 8, "viewers": 2 })));
+    let updates = pump(&transport, &mut session);
+    assert_eq!(updates[0], Ok(Update::Viewers));
+    assert!(updates[1].is_err());
+    assert_eq!(session.viewers, 4);
+
+    server.push(frame("left_game", json!({ "id": 7 })));
+    pump(&transport, &mut session);
+    assert!(!session.spectating);
+    assert_eq!(session.viewers, 0);
+}
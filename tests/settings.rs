@@ -0,0 +1,65 @@
+mod common;
+
+use common::{ frame, player, pump, FakeServer };
+use serde_json::json;
+use tictactoe_client::{
+    protocol::{ ClientEvent, PlayerName },
+    session::Session,
+    settings::{ parse_color, validate_name, NameError, Settings, MAX_NAME_LENGTH },
+    transport::{ LoopbackTransport, Transport },
+};
+
+#[test]
+fn rename_test() {
+    let transport = LoopbackTransport::new();
+    let mut server = FakeServer::new(&transport);
+    let mut session = Session::new();
+
+    server.on("set_name", |content| {
+        let name = serde_json::from_str::<serde_json::Value>(content).unwrap()["name"].clone();
+        let renamed = json!({ "id": 1, "name": name, "joined_game_id": null, "ready": false });
+        vec![frame("players", json!([renamed]))]
+    });
+
+    server.push(frame("players", json!([player(1, "Alice", None)])));
+    pump(&transport, &mut session);
+    session.players[0].set_image("aW1hZ2U=".to_string());
+
+    let name = validate_name("  Alicia ").unwrap();
+    transport.send(&ClientEvent::SetName(PlayerName { name }));
+    server.process();
+    pump(&transport, &mut session);
+    assert_eq!(session.player_name(1), Some("Alicia"));
+    // The avatar survives the rename
+    assert_eq!(session.players[0].image_data(), Some("aW1hZ2U="));
+}
+
+#[test]
+fn validate_name_test() {
+    assert_eq!(validate_name("   "), Err(NameError::Empty));
+    assert_eq!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)), Err(NameError::TooLong));
+    assert_eq!(validate_name("a\nb"), Err(NameError::InvalidCharacter));
+    assert_eq!(validate_name(&"é".repeat(MAX_NAME_LENGTH)).unwrap().chars().count(), 32);
+}
+
+#[test]
+fn settings_test() {
+    assert_eq!(parse_color("#ff8000"), Some([1.0, 128.0 / 255.0, 0.0]));
+    assert_eq!(parse_color("ff8000"), None);
+    assert_eq!(parse_color("#ff80zz"), None);
+
+    let settings = Settings {
+        symbol_color: "#00ff00".to_string(),
+        show_latency: false,
+        show_chat: true,
+    };
+    assert_eq!(Settings::from_json(&settings.to_json()), settings);
+    assert_eq!(settings.body_class(), "hide-latency");
+    assert_eq!(Settings::default().body_class(), "");
+
+    // Missing or broken values fall back to the defaults
+    assert_eq!(Settings::from_json("{\"show_chat\": false}").symbol_color, "#3080ff");
+    assert_eq!(Settings::from_json("not json"), Settings::default());
+    let broken = Settings { symbol_color: "red".to_string(), ..Settings::default() };
+    assert_eq!(broken.symbol_rgb(), Settings::default().symbol_rgb());
+}